@@ -0,0 +1,113 @@
+/// actor.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Implements the actor side of a distributed performance. An actor process plays a single character:
+/// it connects to the director, prepares each part file it is handed with Player::prepare, and answers every
+/// cue with the next line of that part.
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::TcpStream;
+use super::player::Player;
+use super::stage::ScriptEvent;
+use super::director::{Connection, ACTOR_MESSAGE, PART_MESSAGE, LINES_MESSAGE, CUE_MESSAGE, LINE_MESSAGE,
+                      DONE_MESSAGE, ERROR_MESSAGE, PAUSE_SEPARATOR};
+use super::declarations::{NETWORK_ERROR, SCRIPT_PARSING_ERROR};
+
+pub struct Actor {
+    name: String,
+    parts: HashMap<usize, Player>,  // part id -> prepared player for one scene fragment
+}
+
+impl Actor {
+    pub fn new(name: &str) -> Actor {
+        Actor {
+            name: name.to_string(),
+            parts: HashMap::new(),
+        }
+    }
+
+    /// Prepares a part on its own thread, so that a failing Player::prepare is reported instead of aborting
+    fn prepare_part(&self, part_filename: &str) -> Result<Player, u8> {
+        let name = self.name.clone();
+        let filename = part_filename.to_string();
+        let handle = std::thread::spawn(move || {
+            let mut player = Player::new(&name);
+            player.prepare(&filename);
+            player
+        });
+        match handle.join() {
+            Ok(player) => Ok(player),
+            Err(payload) => Err(payload.downcast_ref::<u8>().copied().unwrap_or(SCRIPT_PARSING_ERROR)),
+        }
+    }
+
+    /// Handles a part message, replying with the line numbers of the prepared part and the pauses after them
+    fn receive_part(&mut self, arguments: &str) -> String {
+        let (part_id, part_filename) = match arguments.split_once(' ') {
+            Some((id, filename)) => match id.parse::<usize>() {
+                Ok(id) => (id, filename),
+                Err(_) => return format!("{} {}", ERROR_MESSAGE, NETWORK_ERROR),
+            },
+            None => return format!("{} {}", ERROR_MESSAGE, NETWORK_ERROR),
+        };
+
+        match self.prepare_part(part_filename) {
+            Ok(player) => {
+                let pauses: HashMap<usize, f64> = player.pauses().into_iter().collect();
+                let numbers: Vec<String> = player.lines().iter().map(|(n, _)| match pauses.get(n) {
+                    Some(seconds) => format!("{}{}{}", n, PAUSE_SEPARATOR, seconds),
+                    None => n.to_string(),
+                }).collect();
+                self.parts.insert(part_id, player);
+                format!("{} {}", LINES_MESSAGE, numbers.join(" ")).trim_end().to_string()
+            }
+            Err(error_code) => format!("{} {}", ERROR_MESSAGE, error_code),
+        }
+    }
+
+    /// Handles a cue message by having the player speak its next line
+    fn receive_cue(&mut self, arguments: &str) -> String {
+        let player = match arguments.parse::<usize>().ok().and_then(|id| self.parts.get_mut(&id)) {
+            Some(p) => p,
+            None => return format!("{} {}", ERROR_MESSAGE, NETWORK_ERROR),
+        };
+
+        // Already being the current speaker, the player only performs its line
+        let mut current_speaker = self.name.clone();
+        let mut spoken: Vec<ScriptEvent> = Vec::new();
+        player.speak(&mut current_speaker, &mut spoken);
+        for event in spoken {
+            if let ScriptEvent::Line(line_number, text) = event {
+                return format!("{} {} {}", LINE_MESSAGE, line_number, text);
+            }
+        }
+        format!("{} {}", ERROR_MESSAGE, NETWORK_ERROR)
+    }
+
+    /// Connects to the director and answers its messages until told the performance is done
+    pub fn perform(&mut self, address: &str) -> Result<(), u8> {
+        let stream = match TcpStream::connect(address) {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to connect to '{}': {}", address, e);
+                return Err(NETWORK_ERROR);
+            }
+        };
+        let mut connection = Connection::new(stream)?;
+        connection.send(&format!("{} {}", ACTOR_MESSAGE, self.name))?;
+
+        loop {
+            let (kind, arguments) = connection.receive()?;
+            let reply = match kind.as_str() {
+                PART_MESSAGE => self.receive_part(&arguments),
+                CUE_MESSAGE => self.receive_cue(&arguments),
+                DONE_MESSAGE => return Ok(()),
+                ERROR_MESSAGE => {
+                    let _ = writeln!(std::io::stderr().lock(), "Error: Director rejected actor '{}'", self.name);
+                    return Err(NETWORK_ERROR);
+                }
+                _ => format!("{} {}", ERROR_MESSAGE, NETWORK_ERROR),
+            };
+            connection.send(&reply)?;
+        }
+    }
+}
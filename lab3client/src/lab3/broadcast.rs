@@ -0,0 +1,199 @@
+/// broadcast.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Implements a stage that streams a live recitation to any number of viewers connected over TCP.
+/// Viewers may join at any time; a late joiner is first sent everything since the start of the current scene.
+use std::io::Write;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use super::stage::{Stage, ScriptEvent, TextStage};
+use super::declarations::NETWORK_ERROR;
+
+const VIEWER_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_VIEWER_BACKLOG: usize = 1 << 20;     // bytes queued for a viewer before it is dropped
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A connected viewer. Text for it is queued and written by a thread of its own, so a slow viewer holds up
+/// neither the recitation nor the other viewers. A viewer is dropped when a write to it takes longer than
+/// VIEWER_WRITE_TIMEOUT, when it falls MAX_VIEWER_BACKLOG bytes behind, or when it disconnects.
+struct Viewer {
+    queue: Sender<Vec<u8>>,
+    backlog: Arc<AtomicUsize>,  // bytes queued but not yet written
+    writer: JoinHandle<()>,
+    stream: TcpStream,          // for cutting the writer off at close
+}
+
+impl Viewer {
+    fn start(stream: TcpStream) -> Option<Viewer> {
+        stream.set_write_timeout(Some(VIEWER_WRITE_TIMEOUT)).ok()?;
+        let mut writer_stream = stream.try_clone().ok()?;
+        let (queue, texts) = mpsc::channel::<Vec<u8>>();
+        let backlog = Arc::new(AtomicUsize::new(0));
+        let written = Arc::clone(&backlog);
+        let writer = std::thread::spawn(move || {
+            for text in texts {
+                if writer_stream.write_all(&text).and_then(|_| writer_stream.flush()).is_err() {
+                    break;
+                }
+                written.fetch_sub(text.len(), Ordering::SeqCst);
+            }
+            let _ = writer_stream.shutdown(Shutdown::Both);
+        });
+        Some(Viewer { queue, backlog, writer, stream })
+    }
+
+    /// Queues text for the viewer; false once its writer has given up on it or it has fallen too far behind
+    fn send(&self, text: &[u8]) -> bool {
+        if self.backlog.fetch_add(text.len(), Ordering::SeqCst) + text.len() > MAX_VIEWER_BACKLOG {
+            let _ = self.stream.shutdown(Shutdown::Both);
+            return false;
+        }
+        self.queue.send(text.to_vec()).is_ok()
+    }
+}
+
+/// The connected viewers and the text of the scene currently being performed
+pub struct Audience {
+    viewers: Vec<Viewer>,
+    scene_text: Vec<u8>,
+}
+
+impl Audience {
+    fn new() -> Audience {
+        Audience {
+            viewers: Vec::new(),
+            scene_text: Vec::new(),
+        }
+    }
+
+    /// Catches a new viewer up on the current scene, then adds it to the audience
+    fn admit(&mut self, stream: TcpStream) {
+        if let Some(viewer) = Viewer::start(stream) {
+            if viewer.send(&self.scene_text) {
+                self.viewers.push(viewer);
+            }
+        }
+    }
+
+    /// Sends text to every viewer, dropping the ones that have disconnected or stalled
+    fn send(&mut self, text: &[u8]) {
+        self.scene_text.extend_from_slice(text);
+        self.viewers.retain(|viewer| viewer.send(text));
+    }
+
+    /// Gives every viewer's writer until VIEWER_WRITE_TIMEOUT to finish what is queued for it, then disconnects it
+    fn close(&mut self) {
+        // dropping a viewer's queue ends its writer once everything queued is written
+        let writers: Vec<(JoinHandle<()>, TcpStream)> = self.viewers.drain(..)
+            .map(|viewer| (viewer.writer, viewer.stream))
+            .collect();
+        let deadline = Instant::now() + VIEWER_WRITE_TIMEOUT;
+        for (writer, stream) in writers {
+            while !writer.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(CLOSE_POLL_INTERVAL);
+            }
+            let _ = stream.shutdown(Shutdown::Both);
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Renders each event as plain text, shows it locally and streams it to the audience
+pub struct BroadcastStage {
+    audience: Arc<Mutex<Audience>>,
+    local: TextStage<std::io::Stdout>,
+}
+
+impl BroadcastStage {
+    /// Binds the address and starts admitting viewers on a background thread
+    pub fn open(address: &str) -> Result<BroadcastStage, u8> {
+        let listener = match TcpListener::bind(address) {
+            Ok(l) => l,
+            Err(e) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to bind to '{}': {}", address, e);
+                return Err(NETWORK_ERROR);
+            }
+        };
+
+        let audience = Arc::new(Mutex::new(Audience::new()));
+        let shared_audience = Arc::clone(&audience);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(viewer) => match shared_audience.lock() {
+                        Ok(ref mut audience) => audience.admit(viewer),
+                        Err(_) => return,
+                    },
+                    Err(e) => {
+                        let _ = writeln!(std::io::stderr().lock(), "Error: accept failed: {}", e);
+                    }
+                }
+            }
+        });
+
+        Ok(BroadcastStage {
+            audience,
+            local: TextStage::stdout(),
+        })
+    }
+
+    /// Disconnects every viewer once the performance is over
+    pub fn close(&mut self) {
+        if let Ok(ref mut audience) = self.audience.lock() {
+            audience.close();
+        }
+    }
+}
+
+impl Stage for BroadcastStage {
+    fn perform(&mut self, event: ScriptEvent) {
+        let mut text: Vec<u8> = Vec::new();
+        TextStage::new(&mut text).perform(event.clone());
+
+        match self.audience.lock() {
+            Ok(ref mut audience) => {
+                // A titled fragment starts a new scene, so late joiners start from here
+                if let ScriptEvent::Fragment(_, ref title) = event {
+                    if !title.trim().is_empty() {
+                        audience.scene_text.clear();
+                    }
+                }
+                audience.send(&text);
+            }
+            Err(_) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to lock audience").expect("Failed to write to stderr");
+            }
+        }
+
+        self.local.perform(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_stalled_viewer_is_dropped_without_holding_up_the_rest() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let _stalled = TcpStream::connect(address).unwrap();   // never reads
+        let (stream, _) = listener.accept().unwrap();
+
+        let mut audience = Audience::new();
+        audience.admit(stream);
+        assert_eq!(audience.viewers.len(), 1);
+
+        let started = Instant::now();
+        let chunk = vec![b'x'; 64 * 1024];
+        for _ in 0..(4 * MAX_VIEWER_BACKLOG / chunk.len()) {
+            audience.send(&chunk);
+        }
+        assert!(audience.viewers.is_empty());
+        assert!(started.elapsed() < VIEWER_WRITE_TIMEOUT);
+        audience.close();
+    }
+}
@@ -0,0 +1,283 @@
+/// bundle.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A single-file play bundle holding the script file, the scene configs and the part files as named
+/// members. The bundle is text: a "%lab3-bundle 1" header line, then for every member a
+/// "%member <name> <length> [crc32:<hex>]" line followed by exactly <length> bytes of content and a newline.
+/// Members are opened as <bundle>!<member>, and names inside a member resolve to the same bundle (or tar
+/// archive, whose members are opened the same way).
+use std::fs::{self, File};
+use std::io::{BufRead, Cursor, Read, Write};
+use std::path::{Component, Path};
+use super::play::{Play, ScriptConfig};
+use super::scene_fragment::{SceneFragment, PlayConfig};
+use super::script_gen::get_buffered_reader;
+use super::tar::is_tar_archive;
+use super::declarations::{FAILED_TO_OPEN_FILE, SCRIPT_PARSING_ERROR, FAILED_TO_READ_LINE_FROM_FILE,
+                          FAILED_TO_WRITE_OUTPUT};
+
+pub const BUNDLE_HEADER: &str = "%lab3-bundle 1";
+pub const MEMBER_PREFIX: &str = "%member ";
+pub const CHECKSUM_PREFIX: &str = "crc32:";
+pub const BUNDLE_EXTENSION: &str = ".play";
+pub const MEMBER_SEPARATOR: char = '!';
+
+pub type Members = Vec<(String, Vec<u8>)>;  // (member_name, contents)
+
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// The CRC-32 used by zip and gzip, so other tools can check members
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ CRC32_POLYNOMIAL } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// True for a name that can hold members: a bundle or a tar archive
+pub fn is_container_name(name: &str) -> bool {
+    name.to_lowercase().ends_with(BUNDLE_EXTENSION) || is_tar_archive(name)
+}
+
+/// Splits <container>!<member> into its two parts. Only a '!' after a bundle or tar archive name separates a
+/// member, so other file names may contain '!'
+pub fn split_member(path: &str) -> Option<(&str, &str)> {
+    path.match_indices(MEMBER_SEPARATOR)
+        .map(|(i, _)| (&path[..i], &path[i + MEMBER_SEPARATOR.len_utf8()..]))
+        .find(|(container, member)| is_container_name(container) && !member.is_empty())
+}
+
+/// Resolves a file name read from inside a member: it names another member of the same container. Names are
+/// taken from the container's root, as names in plain files are taken from the working directory, so pack can
+/// store every file under the name the script and configs use for it
+pub fn sibling_path(of: &str, name: &str) -> String {
+    match split_member(of) {
+        Some((container, _)) if split_member(name).is_none() => format!("{}{}{}", container, MEMBER_SEPARATOR, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Script files ending in .play name a bundle whose first member is the script
+pub fn is_bundle_file(filename: &str) -> bool {
+    split_member(filename).is_none() && filename.to_lowercase().ends_with(BUNDLE_EXTENSION)
+}
+
+fn bundle_error(container: &str, message: &str) -> u8 {
+    writeln!(std::io::stderr().lock(), "Error: Invalid bundle '{}': {}", container, message)
+        .expect("Failed to write to stderr");
+    SCRIPT_PARSING_ERROR
+}
+
+/// Parses bundle contents, checking every member that carries a checksum
+pub fn parse_bundle(container: &str, bytes: &[u8]) -> Result<Members, u8> {
+    let mut members: Members = Vec::new();
+    let mut position = 0;
+    let next_line = |position: &mut usize| -> String {
+        let rest = &bytes[*position..];
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        *position += (end + 1).min(rest.len());
+        String::from_utf8_lossy(&rest[..end]).trim_end_matches('\r').to_string()
+    };
+
+    if next_line(&mut position) != BUNDLE_HEADER {
+        return Err(bundle_error(container, &format!("missing '{}' header", BUNDLE_HEADER)));
+    }
+    while position < bytes.len() {
+        let line = next_line(&mut position);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = match line.strip_prefix(MEMBER_PREFIX) {
+            Some(fields) => fields.split_whitespace().collect::<Vec<&str>>(),
+            None => return Err(bundle_error(container, &format!("expected a member line, found '{}'", line))),
+        };
+        let (name, length) = match fields.as_slice() {
+            [name, length] | [name, length, _] => match length.parse::<usize>() {
+                Ok(length) => (name.to_string(), length),
+                Err(_) => return Err(bundle_error(container, &format!("invalid length for '{}'", name))),
+            },
+            _ => return Err(bundle_error(container, &format!("malformed member line '{}'", line))),
+        };
+        if position + length > bytes.len() {
+            return Err(bundle_error(container, &format!("member '{}' is truncated", name)));
+        }
+        let contents = bytes[position..position + length].to_vec();
+        position += length;
+        if bytes.get(position) == Some(&b'\n') {
+            position += 1;
+        }
+
+        if let Some(checksum) = fields.get(2) {
+            let expected = checksum.strip_prefix(CHECKSUM_PREFIX).and_then(|hex| u32::from_str_radix(hex, 16).ok());
+            match expected {
+                Some(expected) if expected == crc32(&contents) => {}
+                Some(_) => return Err(bundle_error(container, &format!("checksum mismatch for '{}'", name))),
+                None => return Err(bundle_error(container, &format!("invalid checksum for '{}'", name))),
+            }
+        }
+        members.push((name, contents));
+    }
+    Ok(members)
+}
+
+/// Reads and parses a whole bundle (from a file or any source get_buffered_reader accepts)
+pub fn read_bundle(container: &String) -> Result<Members, u8> {
+    let mut reader = get_buffered_reader(container)?;
+    let mut bytes: Vec<u8> = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut bytes) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to read '{}': {}", container, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_READ_LINE_FROM_FILE);
+    }
+    parse_bundle(container, &bytes)
+}
+
+/// Opens one member of a bundle for reading
+pub fn open_member(container: &str, member: &str) -> Result<Box<dyn BufRead>, u8> {
+    let members = read_bundle(&container.to_string())?;
+    match members.into_iter().find(|(name, _)| name == member) {
+        Some((_, contents)) => Ok(Box::new(Cursor::new(contents))),
+        None => {
+            writeln!(std::io::stderr().lock(), "Error: '{}' has no member '{}'", container, member)
+                .expect("Failed to write to stderr");
+            Err(FAILED_TO_OPEN_FILE)
+        }
+    }
+}
+
+/// The path of a bundle's script, its first member
+pub fn script_member(container: &String) -> Result<String, u8> {
+    match read_bundle(container)?.first() {
+        Some((name, _)) => Ok(format!("{}{}{}", container, MEMBER_SEPARATOR, name)),
+        None => Err(bundle_error(container, "no members")),
+    }
+}
+
+pub fn write_bundle(members: &Members, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "{}", BUNDLE_HEADER)?;
+    for (name, contents) in members {
+        writeln!(out, "{}{} {} {}{:08x}", MEMBER_PREFIX, name, contents.len(), CHECKSUM_PREFIX, crc32(contents))?;
+        out.write_all(contents)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+fn read_member_file(filename: &String, members: &mut Members) -> Result<(), u8> {
+    if members.iter().any(|(name, _)| name == filename) {
+        return Ok(());
+    }
+    let mut reader = get_buffered_reader(filename)?;
+    let mut contents: Vec<u8> = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut contents) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to read '{}': {}", filename, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_READ_LINE_FROM_FILE);
+    }
+    members.push((filename.clone(), contents));
+    Ok(())
+}
+
+/// Packs a script file with every scene config and part file it names into one bundle
+pub fn pack(script_filename: &String, bundle_filename: &str) -> Result<(), u8> {
+    let mut members: Members = Vec::new();
+    read_member_file(script_filename, &mut members)?;
+
+    let mut script_config: ScriptConfig = Vec::new();
+    Play::new().read_config(script_filename, &mut script_config)?;
+    for (is_scene_title, config_filename) in script_config {
+        if is_scene_title {
+            continue;
+        }
+        read_member_file(&config_filename, &mut members)?;
+        let mut config: PlayConfig = Vec::new();
+        SceneFragment::new(&String::new()).read_config(&config_filename, &mut config)?;
+        for (_, part_filename) in config {
+            read_member_file(&part_filename, &mut members)?;
+        }
+    }
+
+    let result = File::create(bundle_filename).and_then(|mut file| write_bundle(&members, &mut file));
+    if let Err(e) = result {
+        writeln!(std::io::stderr().lock(), "Error: Failed to write '{}': {}", bundle_filename, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_WRITE_OUTPUT);
+    }
+    Ok(())
+}
+
+/// Writes every member of a bundle as a file under directory. Member names that would
+/// leave the directory are refused.
+pub fn unpack(bundle_filename: &String, directory: &str) -> Result<(), u8> {
+    let members = read_bundle(bundle_filename)?;
+    for (name, contents) in &members {
+        let relative = Path::new(name);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(bundle_error(bundle_filename, &format!("member name '{}' leaves the directory", name)));
+        }
+        let path = Path::new(directory).join(relative);
+        let result = match path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }.and_then(|_| fs::write(&path, contents));
+        if let Err(e) = result {
+            writeln!(std::io::stderr().lock(), "Error: Failed to write '{}': {}", path.display(), e)
+                .expect("Failed to write to stderr");
+            return Err(FAILED_TO_WRITE_OUTPUT);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::stage::ScriptEvent;
+
+    // packed from test/test_15/nested_script.txt, whose configs and parts sit in a subdirectory
+    const NESTED_BUNDLE: &str = "test/test_15/nested.play";
+
+    #[test]
+    fn names_resolve_from_the_bundle_root() {
+        assert_eq!(sibling_path("play.play!sub/a_config.txt", "sub/a.txt"), "play.play!sub/a.txt");
+        assert_eq!(sibling_path("play.play!script.txt", "a_config.txt"), "play.play!a_config.txt");
+        assert_eq!(sibling_path("script.txt", "a_config.txt"), "a_config.txt");
+    }
+
+    #[test]
+    fn only_containers_have_members() {
+        assert_eq!(split_member("play.play!script.txt"), Some(("play.play", "script.txt")));
+        assert_eq!(split_member("Play.TAR!dir/a!b.txt"), Some(("Play.TAR", "dir/a!b.txt")));
+        assert_eq!(split_member("net:127.0.0.1:7777:play.tar!a.txt"), Some(("net:127.0.0.1:7777:play.tar", "a.txt")));
+        assert_eq!(split_member("wow!_part.txt"), None);
+        assert_eq!(split_member("play.play!"), None);
+    }
+
+    #[test]
+    fn bundle_round_trip() {
+        let members = read_bundle(&NESTED_BUNDLE.to_string()).unwrap();
+        let mut bytes: Vec<u8> = Vec::new();
+        write_bundle(&members, &mut bytes).unwrap();
+        assert_eq!(parse_bundle(NESTED_BUNDLE, &bytes).unwrap(), members);
+    }
+
+    #[test]
+    fn nested_bundle_recites_every_part() {
+        let members = read_bundle(&NESTED_BUNDLE.to_string()).unwrap();
+        let part_lines = members.iter()
+            .filter(|(name, _)| name.starts_with("sub/") && !name.ends_with("_config.txt"))
+            .map(|(_, contents)| String::from_utf8_lossy(contents).lines().filter(|l| !l.trim().is_empty()).count())
+            .sum::<usize>();
+
+        let mut play = Play::new();
+        play.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        let mut events: Vec<ScriptEvent> = Vec::new();
+        play.recite(&mut events);
+        let lines = events.iter().filter(|event| matches!(event, ScriptEvent::Line(..))).count();
+        assert!(lines > 0);
+        assert_eq!(lines, part_lines);
+    }
+}
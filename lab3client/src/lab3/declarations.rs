@@ -15,8 +15,39 @@ pub const FAILED_TO_OPEN_FILE: u8 = 2;
 pub const SCRIPT_PARSING_ERROR: u8 = 3;
 pub const CONFIG_PARSING_ERROR: u8 = 4;
 pub const FAILED_TO_READ_LINE_FROM_FILE: u8 = 5;
+pub const NETWORK_ERROR: u8 = 6;
+pub const FAILED_TO_WRITE_OUTPUT: u8 = 7;
 pub const SUCCESS: u8 = 0;  
 
+// network modes: program_name director|actor|broadcast <network_address> <script|character> [whinge]
+// (import, pack and unpack take the same shape: program_name <command> <source> <destination> [whinge])
+pub const COMMAND_INDEX: usize = 1;
+pub const DIRECTOR_COMMAND: &str = "director";
+pub const ACTOR_COMMAND: &str = "actor";
+pub const BROADCAST_COMMAND: &str = "broadcast";
+pub const IMPORT_COMMAND: &str = "import";
+pub const PACK_COMMAND: &str = "pack";
+pub const UNPACK_COMMAND: &str = "unpack";
+pub const ROLE_MIN_ARGS: usize = 4;
+pub const ROLE_MAX_ARGS: usize = 5;
+pub const ROLE_ADDRESS_INDEX: usize = 2;
+pub const ROLE_TARGET_INDEX: usize = 3;
+pub const ROLE_VERBOSE_FLAG_INDEX: usize = 4;
+
+// script commands: program_name <command> <script_file_name> [whinge]
+pub const REHEARSE_COMMAND: &str = "rehearse";
+pub const SIDES_COMMAND: &str = "sides";
+pub const BROWSE_COMMAND: &str = "browse";
+pub const REPORT_COMMAND: &str = "report";    // program_name report <history_file>...
+pub const REPORT_MIN_ARGS: usize = 3;
+pub const REPORT_FILES_INDEX: usize = 2;
+pub const COMMANDS: [&str; 10] = [DIRECTOR_COMMAND, ACTOR_COMMAND, BROADCAST_COMMAND, IMPORT_COMMAND, PACK_COMMAND,
+                                  UNPACK_COMMAND, REHEARSE_COMMAND, SIDES_COMMAND, BROWSE_COMMAND, REPORT_COMMAND];
+pub const SCRIPT_COMMAND_MIN_ARGS: usize = 3;
+pub const SCRIPT_COMMAND_MAX_ARGS: usize = 4;
+pub const SCRIPT_COMMAND_FILE_INDEX: usize = 2;
+pub const SCRIPT_COMMAND_VERBOSE_FLAG_INDEX: usize = 3;
+
 pub static WHINGE_MODE: AtomicBool = AtomicBool::new(false);
 
 
@@ -0,0 +1,373 @@
+/// director.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Implements the director side of a distributed performance. The director loads only the play
+/// structure (script file and scene configs), waits for one actor process per character to connect over TCP,
+/// hands each actor its part files, then cues the actors in line number order and assembles the transcript.
+///
+/// Protocol (one message per line):
+///     actor -> director: actor <character_name>
+///     director -> actor: part <part_id> <part_filename>   actor -> director: lines <n1> <n2> ... | error <code>
+///     director -> actor: cue <part_id>                    actor -> director: line <number> <text> | error <code>
+///     director -> actor: done
+/// A line followed by a [pause <seconds>] annotation is listed as <n>@<seconds>, so the director's recitation
+/// pauses where a local one would.
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use super::play::{Play, ScriptConfig};
+use super::player::{Player, PlayLines};
+use super::scene_fragment::{SceneFragment, PlayConfig};
+use super::stage::{Stage, ScriptEvent};
+use super::declarations::NETWORK_ERROR;
+use super::pace::is_valid_pause;
+use super::bundle::{is_bundle_file, script_member};
+
+pub const ACTOR_MESSAGE: &str = "actor";
+pub const PART_MESSAGE: &str = "part";
+pub const LINES_MESSAGE: &str = "lines";
+pub const CUE_MESSAGE: &str = "cue";
+pub const LINE_MESSAGE: &str = "line";
+pub const DONE_MESSAGE: &str = "done";
+pub const ERROR_MESSAGE: &str = "error";
+pub const PAUSE_SEPARATOR: char = '@';
+
+/// A line-oriented message channel over a TCP stream, shared by the director and the actors
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Result<Connection, u8> {
+        match stream.try_clone() {
+            Ok(writer) => Ok(Connection { reader: BufReader::new(stream), writer }),
+            Err(e) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to clone connection: {}", e);
+                Err(NETWORK_ERROR)
+            }
+        }
+    }
+
+    pub fn send(&mut self, message: &str) -> Result<(), u8> {
+        // one write per message: a separate write for the newline waits on the reply's delayed ack
+        if let Err(e) = self.writer.write_all(format!("{}\n", message).as_bytes()).and_then(|_| self.writer.flush()) {
+            let _ = writeln!(std::io::stderr().lock(), "Error: Failed to send '{}': {}", message, e);
+            return Err(NETWORK_ERROR);
+        }
+        Ok(())
+    }
+
+    /// Reads the next message, splitting off its first token; a closed connection is an error
+    pub fn receive(&mut self) -> Result<(String, String), u8> {
+        let mut message = String::new();
+        match self.reader.read_line(&mut message) {
+            Ok(0) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Connection closed unexpectedly");
+                Err(NETWORK_ERROR)
+            }
+            Ok(_) => {
+                let message = message.trim_end_matches(['\r', '\n']);
+                match message.split_once(' ') {
+                    Some((kind, rest)) => Ok((kind.to_string(), rest.to_string())),
+                    None => Ok((message.to_string(), String::new())),
+                }
+            }
+            Err(e) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to read from connection: {}", e);
+                Err(NETWORK_ERROR)
+            }
+        }
+    }
+}
+
+pub type PartIds = HashMap<(usize, String), usize>; // (fragment_index, character_name) -> part id
+
+pub struct Director {
+    scenes: Vec<(String, PlayConfig)>,    // (title, cast) per scene fragment
+    actors: HashMap<String, Connection>,  // character name -> actor process
+}
+
+impl Default for Director {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Director {
+    pub fn new() -> Director {
+        Director {
+            scenes: Vec::new(),
+            actors: HashMap::new(),
+        }
+    }
+
+    /// Reads the script file and every scene config, without opening any part files
+    pub fn prepare(&mut self, script_filename: &String) -> Result<(), u8> {
+        if is_bundle_file(script_filename) {
+            return self.prepare(&script_member(script_filename)?);
+        }
+        let mut config: ScriptConfig = Vec::new();
+        Play::new().read_config(script_filename, &mut config)?;
+
+        let mut title = String::new();
+        for (is_scene_title, text) in &config {
+            if *is_scene_title {
+                title = text.clone();
+            } else {
+                let mut cast: PlayConfig = Vec::new();
+                SceneFragment::new(&title).read_config(text, &mut cast)?;
+                self.scenes.push((title, cast));
+                title = String::new();
+            }
+        }
+
+        // Reuse the play's own validation on an empty skeleton of the fragments
+        let skeleton: Vec<SceneFragment> = self.scenes.iter().map(|(title, _)| SceneFragment::new(title)).collect();
+        Play::from_fragments(skeleton).check_fragments()
+    }
+
+    /// Every distinct character named in the scene configs
+    pub fn cast(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for (_, cast) in &self.scenes {
+            for (name, _) in cast {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        names
+    }
+
+    /// Accepts actor connections on the given address until every character has an actor
+    pub fn gather(&mut self, address: &str) -> Result<(), u8> {
+        let listener = match TcpListener::bind(address) {
+            Ok(l) => l,
+            Err(e) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to bind to '{}': {}", address, e);
+                return Err(NETWORK_ERROR);
+            }
+        };
+
+        let cast = self.cast();
+        while self.actors.len() < cast.len() {
+            let stream = match listener.accept() {
+                Ok((stream, _addr)) => stream,
+                Err(e) => {
+                    let _ = writeln!(std::io::stderr().lock(), "Error: accept failed: {}", e);
+                    continue;
+                }
+            };
+            let mut connection = match Connection::new(stream) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let name = match connection.receive() {
+                Ok((kind, name)) if kind == ACTOR_MESSAGE => name,
+                _ => {
+                    let _ = writeln!(std::io::stderr().lock(), "Warning: Ignoring connection that did not introduce an actor");
+                    continue;
+                }
+            };
+            if !cast.contains(&name) || self.actors.contains_key(&name) {
+                let _ = writeln!(std::io::stderr().lock(), "Warning: Rejecting actor '{}': not an open part", name);
+                let _ = connection.send(&format!("{} {}", ERROR_MESSAGE, NETWORK_ERROR));
+                continue;
+            }
+            self.actors.insert(name, connection);
+        }
+        Ok(())
+    }
+
+    /// Sends each actor its part files and builds the play from the line numbers they report.
+    /// Returns the play together with the part id used for each (fragment_index, character) pair.
+    fn cast_parts(&mut self) -> Result<(Play, PartIds), u8> {
+        let mut fragments: Vec<SceneFragment> = Vec::new();
+        let mut part_ids: PartIds = HashMap::new();
+        let mut next_part_id: usize = 0;
+
+        for (fragment_index, (title, cast)) in self.scenes.iter().enumerate() {
+            let mut players: Vec<Player> = Vec::new();
+            for (name, part_filename) in cast {
+                let part_id = next_part_id;
+                next_part_id += 1;
+                let connection = match self.actors.get_mut(name) {
+                    Some(c) => c,
+                    None => return Err(NETWORK_ERROR),
+                };
+                connection.send(&format!("{} {} {}", PART_MESSAGE, part_id, part_filename))?;
+                let (kind, numbers) = connection.receive()?;
+                if kind != LINES_MESSAGE {
+                    let _ = writeln!(std::io::stderr().lock(), "Error: Actor '{}' could not prepare '{}'", name, part_filename);
+                    return Err(NETWORK_ERROR);
+                }
+
+                // The director only knows where each line falls and the pauses after them, the text stays
+                // with the actor
+                let mut lines: PlayLines = Vec::new();
+                let mut pauses: Vec<(usize, f64)> = Vec::new();
+                for token in numbers.split_whitespace() {
+                    let (number, pause) = match token.split_once(PAUSE_SEPARATOR) {
+                        Some((number, seconds)) => match seconds.parse::<f64>() {
+                            Ok(seconds) if is_valid_pause(seconds) => (number, Some(seconds)),
+                            _ => return Err(NETWORK_ERROR),
+                        },
+                        None => (token, None),
+                    };
+                    match number.parse::<usize>() {
+                        Ok(n) => {
+                            lines.push((n, String::new()));
+                            pauses.extend(pause.map(|seconds| (n, seconds)));
+                        }
+                        Err(_) => return Err(NETWORK_ERROR),
+                    }
+                }
+                let mut player = Player::from_lines(name, lines);
+                for (line_number, seconds) in pauses {
+                    player.add_pause(line_number, seconds);
+                }
+                players.push(player);
+                part_ids.insert((fragment_index, name.clone()), part_id);
+            }
+            fragments.push(SceneFragment::from_players(title, players));
+        }
+
+        Ok((Play::from_fragments(fragments), part_ids))
+    }
+
+    /// Performs the play on the given stage, cueing the actors for every line
+    pub fn perform(&mut self, stage: &mut dyn Stage) -> Result<(), u8> {
+        let (mut play, part_ids) = self.cast_parts()?;
+
+        let mut cue_stage = CueStage {
+            inner: stage,
+            actors: &mut self.actors,
+            part_ids: &part_ids,
+            fragment_index: 0,
+            speaker: String::new(),
+            failed: false,
+        };
+        play.recite(&mut cue_stage);
+        let failed = cue_stage.failed;
+
+        for connection in self.actors.values_mut() {
+            let _ = connection.send(DONE_MESSAGE);
+        }
+
+        if failed {
+            return Err(NETWORK_ERROR);
+        }
+        Ok(())
+    }
+}
+
+/// Sits between the recitation and the real stage, replacing each line's placeholder text
+/// with the text the speaking actor sends back when cued
+struct CueStage<'a> {
+    inner: &'a mut dyn Stage,
+    actors: &'a mut HashMap<String, Connection>,
+    part_ids: &'a PartIds,
+    fragment_index: usize,
+    speaker: String,
+    failed: bool,
+}
+
+impl CueStage<'_> {
+    fn cue(&mut self, line_number: usize) -> Result<String, u8> {
+        let key = (self.fragment_index, self.speaker.clone());
+        let (part_id, connection) = match (self.part_ids.get(&key), self.actors.get_mut(&self.speaker)) {
+            (Some(id), Some(c)) => (id, c),
+            _ => return Err(NETWORK_ERROR),
+        };
+        connection.send(&format!("{} {}", CUE_MESSAGE, part_id))?;
+        let (kind, reply) = connection.receive()?;
+        if kind != LINE_MESSAGE {
+            return Err(NETWORK_ERROR);
+        }
+        match reply.split_once(' ') {
+            Some((number, text)) if number.parse::<usize>() == Ok(line_number) => Ok(text.to_string()),
+            None if reply.parse::<usize>() == Ok(line_number) => Ok(String::new()),
+            _ => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Actor '{}' answered cue {} with '{}'",
+                                 self.speaker, line_number, reply);
+                Err(NETWORK_ERROR)
+            }
+        }
+    }
+}
+
+impl Stage for CueStage<'_> {
+    fn perform(&mut self, event: ScriptEvent) {
+        match event {
+            ScriptEvent::Fragment(index, _) => self.fragment_index = index,
+            ScriptEvent::Speaker(ref name) => self.speaker = name.clone(),
+            ScriptEvent::Line(line_number, _) => {
+                if self.failed {
+                    return;
+                }
+                match self.cue(line_number) {
+                    Ok(text) => self.inner.perform(ScriptEvent::Line(line_number, text)),
+                    Err(_) => {
+                        let _ = writeln!(std::io::stderr().lock(), "Error: No reply from actor '{}' for line {}",
+                                         self.speaker, line_number);
+                        self.failed = true;
+                    }
+                }
+                return;
+            }
+            _ => {}
+        }
+        self.inner.perform(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::actor::Actor;
+
+    // two scene fragments in a bundle, with [pause] annotations in both
+    const NESTED_BUNDLE: &str = "test/test_15/nested.play";
+    const CONNECT_ATTEMPTS: usize = 200;
+
+    /// A localhost address nothing is listening on yet
+    fn free_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn director_and_actors_recite_as_a_local_play_does() {
+        let mut local = Play::new();
+        local.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        let mut expected: Vec<ScriptEvent> = Vec::new();
+        local.recite(&mut expected);
+        assert!(expected.iter().any(|event| matches!(event, ScriptEvent::Pause(_))));
+
+        let address = free_address();
+        let mut director = Director::new();
+        director.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        let actors: Vec<_> = director.cast().into_iter().map(|name| {
+            let address = address.clone();
+            std::thread::spawn(move || {
+                // the director may not be listening yet
+                for _ in 0..CONNECT_ATTEMPTS {
+                    if TcpStream::connect(&address).is_ok_and(|probe| probe.shutdown(std::net::Shutdown::Both).is_ok()) {
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Actor::new(&name).perform(&address)
+            })
+        }).collect();
+
+        director.gather(&address).unwrap();
+        let mut performed: Vec<ScriptEvent> = Vec::new();
+        director.perform(&mut performed).unwrap();
+        for actor in actors {
+            assert_eq!(actor.join().unwrap(), Ok(()));
+        }
+        assert_eq!(performed, expected);
+    }
+}
@@ -0,0 +1,229 @@
+/// epub.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Renders a recitation as an EPUB 3 book for e-readers: one XHTML chapter per scene fragment, a
+/// navigation document built from the scene titles and a dramatis personae page listing every character.
+/// Speeches, speakers and stage directions are marked up with classes rather than layout, so readers can
+/// restyle them. The container is a ZIP with every entry stored uncompressed.
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::stage::{ScriptEvent, fragment_events};
+use super::html::{escape_html, characters_of};
+use super::bundle::crc32;
+use super::zip::ZipWriter;
+
+const MIMETYPE: &str = "application/epub+zip";
+const CONTENT_DIRECTORY: &str = "OEBPS";
+const PACKAGE_FILE: &str = "content.opf";
+const NAV_FILE: &str = "nav.xhtml";
+const CAST_FILE: &str = "cast.xhtml";
+const STYLE_FILE: &str = "style.css";
+const CAST_TITLE: &str = "Dramatis Personae";
+const LANGUAGE: &str = "en";
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+</rootfiles>
+</container>
+";
+
+const STYLE: &str = "body { font-family: serif; line-height: 1.4; }
+h1, h2 { text-align: center; }
+.direction { font-style: italic; margin-left: 2em; }
+.speech { margin: 0.8em 0; }
+.speaker { font-size: 1em; font-variant: small-caps; letter-spacing: 0.05em; margin: 0; }
+.line { margin: 0; }
+.cast { list-style: none; padding: 0; text-align: center; }
+";
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Converts seconds since the Unix epoch to a UTC (year, month, day, hour, minute, second)
+fn utc_date_time(seconds: u64) -> (u64, u64, u64, u64, u64, u64) {
+    // civil date from a day count, after Howard Hinnant's days_from_civil inverse
+    let days = seconds / SECONDS_PER_DAY + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let time = seconds % SECONDS_PER_DAY;
+    (year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+fn chapter_file(fragment_index: usize) -> String {
+    format!("fragment-{}.xhtml", fragment_index)
+}
+
+/// The opening of an XHTML content document, up to and including <body>
+fn write_document_start(title: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+                   lang=\"{0}\" xml:lang=\"{0}\">", LANGUAGE)?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\"/>")?;
+    writeln!(out, "<title>{}</title>", escape_html(title))?;
+    writeln!(out, "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}\"/>", STYLE_FILE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")
+}
+
+fn write_document_end(out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+/// One chapter: a fragment's events from its Fragment event up to the next one
+fn write_chapter(events: &[ScriptEvent], play_title: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    let title = match events.first() {
+        Some(ScriptEvent::Fragment(_, title)) if !title.trim().is_empty() => title.as_str(),
+        _ => play_title,
+    };
+    write_document_start(title, out)?;
+    writeln!(out, "<section epub:type=\"chapter\">")?;
+    if let Some(ScriptEvent::Fragment(_, title)) = events.first() {
+        if !title.trim().is_empty() {
+            writeln!(out, "<h2>{}</h2>", escape_html(title))?;
+        }
+    }
+
+    let mut in_speech = false;
+    for event in events {
+        // Any event other than a line ends the speech in progress
+        if in_speech && !matches!(event, ScriptEvent::Line(_, _) | ScriptEvent::Pause(_)) {
+            writeln!(out, "</div>")?;
+            in_speech = false;
+        }
+        match event {
+            ScriptEvent::Enter(name) => {
+                writeln!(out, "<p class=\"direction\" role=\"note\">Enter {}.</p>", escape_html(name))?;
+            }
+            ScriptEvent::Exit(name) => {
+                writeln!(out, "<p class=\"direction\" role=\"note\">Exit {}.</p>", escape_html(name))?;
+            }
+            ScriptEvent::Speaker(name) => {
+                writeln!(out, "<div class=\"speech\">")?;
+                writeln!(out, "<h3 class=\"speaker\">{}</h3>", escape_html(name))?;
+                in_speech = true;
+            }
+            ScriptEvent::Line(_, text) => writeln!(out, "<p class=\"line\">{}</p>", escape_html(text))?,
+            ScriptEvent::Fragment(_, _) | ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_)
+                | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    if in_speech {
+        writeln!(out, "</div>")?;
+    }
+    writeln!(out, "</section>")?;
+    write_document_end(out)
+}
+
+fn write_cast(characters: &[String], out: &mut dyn Write) -> std::io::Result<()> {
+    write_document_start(CAST_TITLE, out)?;
+    writeln!(out, "<section epub:type=\"dramatis-personae\">")?;
+    writeln!(out, "<h2>{}</h2>", CAST_TITLE)?;
+    writeln!(out, "<ul class=\"cast\">")?;
+    for name in characters {
+        writeln!(out, "<li>{}</li>", escape_html(name))?;
+    }
+    writeln!(out, "</ul>")?;
+    writeln!(out, "</section>")?;
+    write_document_end(out)
+}
+
+/// The navigation document, listing the cast page and every titled fragment
+fn write_nav(chapters: &[(usize, String)], play_title: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    write_document_start(play_title, out)?;
+    writeln!(out, "<nav epub:type=\"toc\" id=\"toc\">")?;
+    writeln!(out, "<h1>{}</h1>", escape_html(play_title))?;
+    writeln!(out, "<ol>")?;
+    writeln!(out, "<li><a href=\"{}\">{}</a></li>", CAST_FILE, CAST_TITLE)?;
+    for (index, title) in chapters {
+        if !title.trim().is_empty() {
+            writeln!(out, "<li><a href=\"{}\">{}</a></li>", chapter_file(*index), escape_html(title))?;
+        }
+    }
+    writeln!(out, "</ol>")?;
+    writeln!(out, "</nav>")?;
+    write_document_end(out)
+}
+
+fn write_package(chapters: &[(usize, String)], play_title: &str, identifier: &str, modified: &str,
+                 out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">")?;
+    writeln!(out, "<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">")?;
+    writeln!(out, "<dc:identifier id=\"book-id\">{}</dc:identifier>", identifier)?;
+    writeln!(out, "<dc:title>{}</dc:title>", escape_html(play_title))?;
+    writeln!(out, "<dc:language>{}</dc:language>", LANGUAGE)?;
+    writeln!(out, "<meta property=\"dcterms:modified\">{}</meta>", modified)?;
+    writeln!(out, "</metadata>")?;
+    writeln!(out, "<manifest>")?;
+    writeln!(out, "<item id=\"nav\" href=\"{}\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>", NAV_FILE)?;
+    writeln!(out, "<item id=\"style\" href=\"{}\" media-type=\"text/css\"/>", STYLE_FILE)?;
+    writeln!(out, "<item id=\"cast\" href=\"{}\" media-type=\"application/xhtml+xml\"/>", CAST_FILE)?;
+    for (index, _) in chapters {
+        writeln!(out, "<item id=\"fragment-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+                 index, chapter_file(*index))?;
+    }
+    writeln!(out, "</manifest>")?;
+    writeln!(out, "<spine>")?;
+    writeln!(out, "<itemref idref=\"nav\"/>")?;
+    writeln!(out, "<itemref idref=\"cast\"/>")?;
+    for (index, _) in chapters {
+        writeln!(out, "<itemref idref=\"fragment-{}\"/>", index)?;
+    }
+    writeln!(out, "</spine>")?;
+    writeln!(out, "</package>")
+}
+
+/// Writes the whole recitation as an EPUB book titled after the first fragment
+pub fn write_epub(events: &[ScriptEvent], out: &mut dyn Write) -> std::io::Result<()> {
+    let characters = characters_of(events);
+    let play_title = events.iter().find_map(|event| match event {
+        ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => Some(title.clone()),
+        _ => None,
+    }).unwrap_or_default();
+
+    // Split the recitation at its fragments, one chapter each
+    let mut chapters: Vec<(usize, String)> = Vec::new();
+    let mut chapter_documents: Vec<Vec<u8>> = Vec::new();
+    for fragment in fragment_events(events) {
+        if let Some(ScriptEvent::Fragment(index, title)) = fragment.first() {
+            let mut document: Vec<u8> = Vec::new();
+            write_chapter(fragment, &play_title, &mut document)?;
+            chapters.push((*index, title.clone()));
+            chapter_documents.push(document);
+        }
+    }
+
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day, hour, minute, second) = utc_date_time(seconds);
+    let modified = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second);
+    let identifier = format!("urn:lab3:{:08x}", crc32(&chapter_documents.concat()));
+
+    let mut nav: Vec<u8> = Vec::new();
+    write_nav(&chapters, &play_title, &mut nav)?;
+    let mut cast: Vec<u8> = Vec::new();
+    write_cast(&characters, &mut cast)?;
+    let mut package: Vec<u8> = Vec::new();
+    write_package(&chapters, &play_title, &identifier, &modified, &mut package)?;
+
+    // The mimetype must come first, so readers can recognize the file from its opening bytes
+    let mut zip = ZipWriter::new(year, month, day, hour, minute, second);
+    zip.add("mimetype", MIMETYPE.as_bytes().to_vec());
+    zip.add("META-INF/container.xml", CONTAINER.as_bytes().to_vec());
+    zip.add(&format!("{}/{}", CONTENT_DIRECTORY, PACKAGE_FILE), package);
+    zip.add(&format!("{}/{}", CONTENT_DIRECTORY, NAV_FILE), nav);
+    zip.add(&format!("{}/{}", CONTENT_DIRECTORY, STYLE_FILE), STYLE.as_bytes().to_vec());
+    zip.add(&format!("{}/{}", CONTENT_DIRECTORY, CAST_FILE), cast);
+    for ((index, _), document) in chapters.iter().zip(chapter_documents) {
+        zip.add(&format!("{}/{}", CONTENT_DIRECTORY, chapter_file(*index)), document);
+    }
+    zip.finish(out)
+}
@@ -0,0 +1,110 @@
+/// export.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Chooses an output format for a play and writes it to stdout or a file. Most formats render a recorded
+/// recitation; json dumps the play model itself and csv and tsv list its lines.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use super::stage::{Stage, ScriptEvent, TextStage};
+use super::html::write_html;
+use super::markup::{write_markdown, write_rst};
+use super::fountain::write_fountain;
+use super::epub::write_epub;
+use super::pdf::write_pdf;
+use super::latex::write_latex;
+use super::subtitles::{write_srt, write_vtt, write_vtt_chapters};
+use super::model::play_to_json;
+use super::sheet::{sheet_rows, write_sheet, CSV_DELIMITER, TSV_DELIMITER};
+use super::play::Play;
+use super::options::RecitalOptions;
+use super::declarations::{BAD_COMMAND_LINE_ERROR, FAILED_TO_WRITE_OUTPUT};
+
+pub const TEXT_FORMAT: &str = "text";
+pub const HTML_FORMAT: &str = "html";
+pub const MARKDOWN_FORMAT: &str = "markdown";
+pub const RST_FORMAT: &str = "rst";
+pub const JSON_FORMAT: &str = "json";
+pub const FOUNTAIN_FORMAT: &str = "fountain";
+pub const EPUB_FORMAT: &str = "epub";
+pub const PDF_FORMAT: &str = "pdf";
+pub const LATEX_FORMAT: &str = "latex";
+pub const SRT_FORMAT: &str = "srt";
+pub const VTT_FORMAT: &str = "vtt";
+pub const VTT_CHAPTERS_FORMAT: &str = "vtt-chapters";
+pub const SSML_FORMAT: &str = "ssml";       // one file per fragment, so --output names a directory
+pub const CSV_FORMAT: &str = "csv";
+pub const TSV_FORMAT: &str = "tsv";
+pub const FORMATS: [&str; 15] = [TEXT_FORMAT, HTML_FORMAT, MARKDOWN_FORMAT, RST_FORMAT, JSON_FORMAT, FOUNTAIN_FORMAT,
+                                 EPUB_FORMAT, PDF_FORMAT, LATEX_FORMAT, SRT_FORMAT, VTT_FORMAT, VTT_CHAPTERS_FORMAT,
+                                 SSML_FORMAT, CSV_FORMAT, TSV_FORMAT];
+
+/// Opens the output file, or stdout when no file is given
+pub fn open_output(output: Option<&str>) -> Result<Box<dyn Write>, u8> {
+    match output {
+        Some(filename) => match File::create(filename) {
+            Ok(file) => Ok(Box::new(BufWriter::new(file))),
+            Err(e) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to create output file '{}': {}", filename, e)
+                    .expect("Failed to write to stderr");
+                Err(FAILED_TO_WRITE_OUTPUT)
+            }
+        },
+        None => Ok(Box::new(std::io::stdout())),
+    }
+}
+
+pub fn is_format(format: &str) -> bool {
+    FORMATS.contains(&format)
+}
+
+/// Writes the play in the given format, reciting it first unless the format describes the model
+pub fn export(format: &str, play: &mut Play, options: &RecitalOptions, out: &mut dyn Write) -> Result<(), u8> {
+    if format == JSON_FORMAT {
+        let model = play_to_json(play)?;
+        return finish(format, model.write(out, 0).and_then(|_| writeln!(out)), out);
+    }
+    if format == CSV_FORMAT || format == TSV_FORMAT {
+        let delimiter = if format == CSV_FORMAT { CSV_DELIMITER } else { TSV_DELIMITER };
+        let rows = sheet_rows(play)?;
+        return finish(format, write_sheet(&rows, delimiter, out), out);
+    }
+
+    let mut events: Vec<ScriptEvent> = Vec::new();
+    play.recite(&mut events);
+    let events = &events;
+    let pace = options.pace.clone().unwrap_or_default();     // subtitles are timed at the pacing options' rate
+    let result = match format {
+        TEXT_FORMAT => {
+            let mut stage = TextStage::new(&mut *out).with_numbering(options.numbering);
+            for event in events {
+                stage.perform(event.clone());
+            }
+            Ok(())
+        }
+        HTML_FORMAT => write_html(events, options.numbering, out),
+        MARKDOWN_FORMAT => write_markdown(events, options.line_anchors, out),
+        RST_FORMAT => write_rst(events, options.line_anchors, out),
+        FOUNTAIN_FORMAT => write_fountain(events, out),
+        EPUB_FORMAT => write_epub(events, out),
+        PDF_FORMAT => write_pdf(events, options.numbering, out),
+        LATEX_FORMAT => write_latex(events, out),
+        SRT_FORMAT => write_srt(events, &pace, out),
+        VTT_FORMAT => write_vtt(events, &pace, out),
+        VTT_CHAPTERS_FORMAT => write_vtt_chapters(events, &pace, out),
+        _ => {
+            writeln!(std::io::stderr().lock(), "Error: Unknown format '{}' (expected one of: {})", format, FORMATS.join(", "))
+                .expect("Failed to write to stderr");
+            return Err(BAD_COMMAND_LINE_ERROR);
+        }
+    };
+    finish(format, result, out)
+}
+
+/// Flushes the output, reporting any write error
+fn finish(format: &str, result: std::io::Result<()>, out: &mut dyn Write) -> Result<(), u8> {
+    if let Err(e) = result.and_then(|_| out.flush()) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to write {} output: {}", format, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_WRITE_OUTPUT);
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+/// fountain.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Converts between recitations and the Fountain screenplay format. Export writes fragment titles as
+/// forced scene headings, speakers as character cues and enter/exit directives as action lines; an untitled
+/// fragment that continues a scene starts after a page break. Import reads scene headings, page breaks and
+/// dialogue back into scene fragments, numbering each fragment's dialogue lines from 1; action, transitions,
+/// parentheticals, sections, synopses, notes and boneyard are skipped.
+use std::io::Write;
+use super::stage::ScriptEvent;
+use super::play::Play;
+use super::scene_fragment::SceneFragment;
+use super::import::SceneDraft;
+
+const TITLE_KEY: &str = "title";
+const FORCED_SCENE_HEADING: char = '.';
+const FORCED_CHARACTER: char = '@';
+const FORCED_ACTION: char = '!';
+const DUAL_DIALOGUE_MARK: char = '^';
+const LYRIC_MARK: char = '~';
+const PAGE_BREAK: &str = "===";
+const SCENE_HEADING_PREFIXES: [&str; 6] = ["INT./EXT", "INT/EXT", "I/E", "INT", "EXT", "EST"];
+
+/// Writes the recitation as a Fountain screenplay
+pub fn write_fountain(events: &[ScriptEvent], out: &mut dyn Write) -> std::io::Result<()> {
+    let play_title = events.iter().find_map(|event| match event {
+        ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => Some(title.clone()),
+        _ => None,
+    }).unwrap_or_default();
+    writeln!(out, "Title: {}", play_title)?;
+
+    let mut in_action = false;
+    for event in events {
+        match event {
+            ScriptEvent::Fragment(index, title) => {
+                if !title.trim().is_empty() {
+                    writeln!(out)?;
+                    writeln!(out, "{}{}", FORCED_SCENE_HEADING, title.trim_start_matches(FORCED_SCENE_HEADING))?;
+                } else if *index > 0 {
+                    writeln!(out)?;
+                    writeln!(out, "{}", PAGE_BREAK)?;
+                }
+                in_action = false;
+            }
+            ScriptEvent::Enter(name) | ScriptEvent::Exit(name) => {
+                if !in_action {
+                    writeln!(out)?;
+                }
+                let verb = if matches!(event, ScriptEvent::Enter(_)) { "Enter" } else { "Exit" };
+                writeln!(out, "{} {}.", verb, name)?;
+                in_action = true;
+            }
+            ScriptEvent::Speaker(name) => {
+                writeln!(out)?;
+                if name.chars().any(char::is_lowercase) {
+                    writeln!(out, "{}{}", FORCED_CHARACTER, name)?;
+                } else {
+                    writeln!(out, "{}", name)?;
+                }
+                in_action = false;
+            }
+            ScriptEvent::Line(_, text) => writeln!(out, "{}", text)?,
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    Ok(())
+}
+
+/// Removes everything between open and close, which may span lines
+fn remove_spans(text: &str, open: &str, close: &str) -> String {
+    let mut kept = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        kept.push_str(&rest[..start]);
+        match rest[start + open.len()..].find(close) {
+            Some(end) => rest = &rest[start + open.len() + end + close.len()..],
+            None => {
+                rest = "";
+                break;
+            }
+        }
+    }
+    kept.push_str(rest);
+    kept
+}
+
+fn is_scene_heading(line: &str) -> bool {
+    let upper = line.to_uppercase();
+    SCENE_HEADING_PREFIXES.iter().any(|prefix| {
+        upper.strip_prefix(prefix).map(|rest| rest.starts_with('.') || rest.starts_with(' ')).unwrap_or(false)
+    })
+}
+
+fn is_transition(line: &str) -> bool {
+    (line.starts_with('>') && !line.ends_with('<'))
+        || (line.ends_with("TO:") && !line.chars().any(char::is_lowercase))
+}
+
+/// Cues are all upper case outside any parenthesized extension, such as (V.O.) or (cont'd)
+fn is_character_cue(line: &str) -> bool {
+    let name = line.split('(').next().unwrap_or_default();
+    name.chars().any(char::is_alphabetic) && !name.chars().any(char::is_lowercase)
+}
+
+fn cue_name(line: &str) -> String {
+    let name = line.trim_start_matches(FORCED_CHARACTER).trim_end_matches(DUAL_DIALOGUE_MARK);
+    name.split('(').next().unwrap_or_default().trim().to_string()
+}
+
+/// Strips the forcing mark and any trailing #scene number# from a scene heading
+fn heading_title(line: &str) -> String {
+    let title = line.strip_prefix(FORCED_SCENE_HEADING).unwrap_or(line).trim();
+    match title.strip_suffix('#').and_then(|t| t.rfind('#').map(|start| &t[..start])) {
+        Some(without_number) => without_number.trim().to_string(),
+        None => title.to_string(),
+    }
+}
+
+/// Reads the Title: value of the title page, if the document starts with one. Returns it with the
+/// number of lines the title page takes.
+fn title_page(lines: &[&str]) -> (Option<String>, usize) {
+    let is_key = |line: &str| line.split_once(':').map(|(key, _)| {
+        !key.is_empty() && !key.starts_with(char::is_whitespace) && key.chars().all(|c| c.is_alphanumeric() || c == ' ')
+    }).unwrap_or(false);
+    if !lines.first().map(|line| is_key(line)).unwrap_or(false) {
+        return (None, 0);
+    }
+
+    let mut title: Option<String> = None;
+    let mut in_title = false;
+    let mut count = 0;
+    for line in lines {
+        if line.trim().is_empty() {
+            break;
+        }
+        count += 1;
+        if is_key(line) && !line.starts_with(char::is_whitespace) {
+            let (key, value) = line.split_once(':').unwrap_or_default();
+            in_title = key.trim().eq_ignore_ascii_case(TITLE_KEY);
+            if in_title && !value.trim().is_empty() {
+                title = Some(value.trim().to_string());
+            }
+        } else if in_title {
+            // indented continuation of the title
+            let value = line.trim();
+            title = Some(match title {
+                Some(t) => format!("{} {}", t, value),
+                None => value.to_string(),
+            });
+        }
+    }
+    (title, count)
+}
+
+/// Parses a Fountain document into a play. Dialogue before the first scene heading is titled
+/// after the title page, or after default_title when there is none.
+pub fn parse_fountain(text: &str, default_title: &str) -> Result<Play, String> {
+    let text = text.replace("\r\n", "\n");
+    let text = remove_spans(&remove_spans(&text, "/*", "*/"), "[[", "]]");
+    let lines: Vec<&str> = text.lines().collect();
+    let (title, title_page_length) = title_page(&lines);
+    let default_title = title.unwrap_or_else(|| default_title.to_string());
+
+    let mut fragments: Vec<SceneFragment> = Vec::new();
+    let mut scene = SceneDraft::new(String::new());
+    let mut speaker: Option<String> = None;
+    let mut previous_blank = true;
+
+    for (i, raw_line) in lines.iter().enumerate().skip(title_page_length) {
+        let line = raw_line.trim();
+        if line.is_empty() {
+            speaker = None;
+            previous_blank = true;
+            continue;
+        }
+        let next_blank = lines.get(i + 1).map(|next| next.trim().is_empty()).unwrap_or(true);
+
+        if let Some(ref name) = speaker {
+            // dialogue continues until a blank line; parentheticals are stage business
+            if !(line.starts_with('(') && line.ends_with(')')) {
+                scene.add_line(name, None, line.trim_start_matches(LYRIC_MARK).trim());
+            }
+        } else if line.starts_with(PAGE_BREAK) && line.chars().all(|c| c == '=') {
+            // a page break inside a scene continues it in a new, untitled fragment
+            if scene.has_lines() {
+                let previous = std::mem::replace(&mut scene, SceneDraft::new(String::new()));
+                previous.finish(&mut fragments, &default_title);
+            }
+        } else if line.starts_with(FORCED_ACTION) || line.starts_with('#') || line.starts_with('=') {
+            // forced action, section or synopsis
+        } else if (line.starts_with(FORCED_SCENE_HEADING) && !line.starts_with(".."))
+            || (previous_blank && is_scene_heading(line)) {
+            let previous = std::mem::replace(&mut scene, SceneDraft::new(heading_title(line)));
+            previous.finish(&mut fragments, &default_title);
+        } else if is_transition(line) {
+            // transitions carry no dialogue
+        } else if previous_blank && !next_blank && (line.starts_with(FORCED_CHARACTER) || is_character_cue(line)) {
+            let name = cue_name(line);
+            if name.is_empty() {
+                return Err(format!("empty character cue on line {}", i + 1));
+            }
+            speaker = Some(name);
+        }
+        previous_blank = false;
+    }
+    scene.finish(&mut fragments, &default_title);
+
+    if fragments.is_empty() {
+        return Err("no dialogue found".to_string());
+    }
+    Ok(Play::from_fragments(fragments))
+}
@@ -0,0 +1,161 @@
+/// html.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Renders a recitation as one self-contained HTML page with a table of contents built from the
+/// scene titles, a heading for every speech, italic enter and exit directives, an anchor for every line and
+/// a consistent color per character. Line numbers show when a line is hovered or linked to, or always in the
+/// margin for the lines the numbering labels.
+use std::io::Write;
+use super::stage::ScriptEvent;
+use super::numbering::{Numbering, LineLabels};
+
+const STYLE: &str = "body { font-family: Georgia, serif; max-width: 48em; margin: 2em auto; line-height: 1.5; }
+nav ol { padding-left: 1.5em; }
+section h2 { text-align: center; margin-top: 2.5em; }
+.direction { font-style: italic; color: #555; margin-left: 2em; }
+.speech { margin: 1em 0; padding-left: 0.75em; border-left: 4px solid var(--character-color); }
+.speaker { font-size: 1em; font-variant: small-caps; letter-spacing: 0.05em; margin: 0; color: var(--character-color); }
+.line { margin: 0; position: relative; }
+.line-number { position: absolute; left: -4.5em; width: 3em; text-align: right; color: #999;
+               font-size: 0.8em; text-decoration: none; visibility: hidden; }
+.line:hover .line-number, .line:target .line-number, .line-number.margin { visibility: visible; }
+.line-number.citation { left: -7.5em; width: 6em; }
+.line:target { background: #ffd; }
+";
+
+const SATURATION: u32 = 55;         // percent
+const LIGHTNESS: u32 = 38;          // percent
+const GOLDEN_ANGLE: usize = 137;    // degrees between consecutive character hues, to keep them apart
+const FULL_CIRCLE: usize = 360;
+
+/// Escapes text for use in HTML content and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Every character that enters or speaks, in order of first appearance
+pub fn characters_of(events: &[ScriptEvent]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for event in events {
+        if let ScriptEvent::Enter(name) | ScriptEvent::Speaker(name) = event {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+/// Id of the anchor for a fragment, and of a line within it (line numbers restart in every fragment)
+pub fn fragment_anchor(fragment_index: usize) -> String {
+    format!("fragment-{}", fragment_index)
+}
+
+pub fn line_anchor(fragment_index: usize, line_number: usize) -> String {
+    format!("fragment-{}-line-{}", fragment_index, line_number)
+}
+
+/// Writes the whole recitation as an HTML document titled after the first fragment
+pub fn write_html(events: &[ScriptEvent], numbering: Numbering, out: &mut dyn Write) -> std::io::Result<()> {
+    let characters = characters_of(events);
+    let play_title = events.iter().find_map(|event| match event {
+        ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => Some(title.clone()),
+        _ => None,
+    }).unwrap_or_default();
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape_html(&play_title))?;
+    writeln!(out, "<style>")?;
+    write!(out, "{}", STYLE)?;
+    for (index, _) in characters.iter().enumerate() {
+        writeln!(out, ".character-{} {{ --character-color: hsl({}, {}%, {}%); }}",
+                 index, index * GOLDEN_ANGLE % FULL_CIRCLE, SATURATION, LIGHTNESS)?;
+    }
+    writeln!(out, "</style>")?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+
+    // Table of contents from the titled fragments
+    writeln!(out, "<nav>")?;
+    writeln!(out, "<h1>{}</h1>", escape_html(&play_title))?;
+    writeln!(out, "<ol>")?;
+    for event in events {
+        if let ScriptEvent::Fragment(index, title) = event {
+            if !title.trim().is_empty() {
+                writeln!(out, "<li><a href=\"#{}\">{}</a></li>", fragment_anchor(*index), escape_html(title))?;
+            }
+        }
+    }
+    writeln!(out, "</ol>")?;
+    writeln!(out, "</nav>")?;
+    writeln!(out, "<main>")?;
+
+    let mut labels = LineLabels::new(numbering);
+    let mut fragment_index: usize = 0;
+    let mut in_section = false;
+    let mut in_speech = false;
+    for event in events {
+        labels.observe(event);
+        // Any event other than a line ends the speech in progress
+        if in_speech && !matches!(event, ScriptEvent::Line(_, _) | ScriptEvent::Pause(_)) {
+            writeln!(out, "</div>")?;
+            in_speech = false;
+        }
+        match event {
+            ScriptEvent::Fragment(index, title) => {
+                if in_section {
+                    writeln!(out, "</section>")?;
+                }
+                fragment_index = *index;
+                writeln!(out, "<section id=\"{}\">", fragment_anchor(fragment_index))?;
+                in_section = true;
+                if !title.trim().is_empty() {
+                    writeln!(out, "<h2>{}</h2>", escape_html(title))?;
+                }
+            }
+            ScriptEvent::Enter(name) => {
+                writeln!(out, "<p class=\"direction\">Enter {}.</p>", escape_html(name))?;
+            }
+            ScriptEvent::Exit(name) => {
+                writeln!(out, "<p class=\"direction\">Exit {}.</p>", escape_html(name))?;
+            }
+            ScriptEvent::Speaker(name) => {
+                let index = characters.iter().position(|c| c == name).unwrap_or(0);
+                writeln!(out, "<div class=\"speech character-{}\">", index)?;
+                writeln!(out, "<h3 class=\"speaker\">{}</h3>", escape_html(name))?;
+                in_speech = true;
+            }
+            ScriptEvent::Line(line_number, text) => {
+                let anchor = line_anchor(fragment_index, *line_number);
+                let (class, label) = match labels.label(*line_number) {
+                    Some(label) if numbering.citations => ("line-number margin citation", label),
+                    Some(label) => ("line-number margin", label),
+                    None => ("line-number", line_number.to_string()),
+                };
+                writeln!(out, "<p class=\"line\" id=\"{0}\"><a class=\"{1}\" href=\"#{0}\">{2}</a>{3}</p>",
+                         anchor, class, escape_html(&label), escape_html(text))?;
+            }
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    if in_section {
+        writeln!(out, "</section>")?;
+    }
+
+    writeln!(out, "</main>")?;
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
@@ -0,0 +1,228 @@
+/// import.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Converts documents in other formats into this project's layout: a script file listing scene titles
+/// and scene configs, one config per scene naming each character's part file, and numbered part files.
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use super::play::Play;
+use super::player::{Player, PlayLines};
+use super::scene_fragment::SceneFragment;
+use super::fountain::parse_fountain;
+use super::tei::parse_tei;
+use super::transcript::parse_transcript;
+use super::sheet::{parse_sheet, CSV_DELIMITER, TSV_DELIMITER};
+use super::script_gen::get_buffered_reader;
+use super::declarations::{WHINGE_MODE, BAD_COMMAND_LINE_ERROR, SCRIPT_PARSING_ERROR, FAILED_TO_READ_LINE_FROM_FILE,
+                          FAILED_TO_WRITE_OUTPUT};
+
+pub const FOUNTAIN_EXTENSION: &str = "fountain";
+pub const TEI_EXTENSION: &str = "tei";
+pub const XML_EXTENSION: &str = "xml";
+pub const TEXT_EXTENSION: &str = "txt";
+pub const TRANSCRIPT_EXTENSION: &str = "transcript";
+pub const CSV_EXTENSION: &str = "csv";
+pub const TSV_EXTENSION: &str = "tsv";
+pub const IMPORT_EXTENSIONS: [&str; 7] = [FOUNTAIN_EXTENSION, TEI_EXTENSION, XML_EXTENSION, TEXT_EXTENSION,
+                                          TRANSCRIPT_EXTENSION, CSV_EXTENSION, TSV_EXTENSION];
+
+pub const SCRIPT_FILE_SUFFIX: &str = "_script.txt";
+pub const CONFIG_FILE_SUFFIX: &str = "_config.txt";
+pub const PART_FILE_EXTENSION: &str = ".txt";
+pub const FIRST_LINE_NUMBER: usize = 1;
+
+/// Scene being imported: its title and each speaker's lines, in order of first speech
+pub struct SceneDraft {
+    title: String,
+    parts: Vec<(String, PlayLines)>,
+    pauses: Vec<(String, usize, f64)>,     // (speaker, line_number, seconds)
+    next_line: usize,
+}
+
+impl SceneDraft {
+    pub fn new(title: String) -> SceneDraft {
+        SceneDraft { title, parts: Vec::new(), pauses: Vec::new(), next_line: FIRST_LINE_NUMBER }
+    }
+
+    pub fn has_lines(&self) -> bool {
+        self.parts.iter().any(|(_, lines)| !lines.is_empty())
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    /// Adds a character who may have no lines in this scene
+    pub fn add_player(&mut self, name: &str) {
+        if !self.parts.iter().any(|(part, _)| part == name) {
+            self.parts.push((name.to_string(), Vec::new()));
+        }
+    }
+
+    /// Adds a line with the given number if it keeps the lines in order, or else the next number.
+    /// Returns the number the line was given.
+    pub fn add_line(&mut self, speaker: &str, number: Option<usize>, text: &str) -> usize {
+        let number = match number {
+            Some(n) if n >= self.next_line => n,
+            _ => self.next_line,
+        };
+        self.next_line = number + 1;
+        let line = (number, text.to_string());
+        match self.parts.iter_mut().find(|(name, _)| name == speaker) {
+            Some((_, lines)) => lines.push(line),
+            None => self.parts.push((speaker.to_string(), vec![line])),
+        }
+        number
+    }
+
+    /// Pauses for the given seconds after a line already added
+    pub fn add_pause(&mut self, speaker: &str, number: usize, seconds: f64) {
+        self.pauses.push((speaker.to_string(), number, seconds));
+    }
+
+    /// Adds the scene to fragments unless it has no lines; an untitled opening scene takes the default title
+    pub fn finish(mut self, fragments: &mut Vec<SceneFragment>, default_title: &str) {
+        if !self.has_lines() {
+            if WHINGE_MODE.load(Ordering::SeqCst) && !self.title.is_empty() {
+                writeln!(std::io::stderr().lock(), "Warning: Scene '{}' has no dialogue and was skipped", self.title)
+                    .expect("Failed to write to stderr");
+            }
+            return;
+        }
+        if fragments.is_empty() && self.title.is_empty() {
+            self.title = default_title.to_string();
+        }
+        let pauses = self.pauses;
+        let players = self.parts.into_iter().map(|(name, lines)| {
+            let mut player = Player::from_lines(&name, lines);
+            for (_, line_number, seconds) in pauses.iter().filter(|(speaker, _, _)| *speaker == name) {
+                player.add_pause(*line_number, *seconds);
+            }
+            player
+        }).collect();
+        fragments.push(SceneFragment::from_players(&self.title, players));
+    }
+}
+
+/// Reads a whole source document (from a file or any source get_buffered_reader accepts)
+pub fn read_source(source: &String) -> Result<String, u8> {
+    let mut reader = get_buffered_reader(source)?;
+    let mut text = String::new();
+    if let Err(e) = reader.read_to_string(&mut text) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to read '{}': {}", source, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_READ_LINE_FROM_FILE);
+    }
+    Ok(text)
+}
+
+/// Names in configs are single tokens and also appear in file names
+pub fn part_token(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<&str>>().join("_")
+}
+
+fn file_token(name: &str) -> String {
+    part_token(name).chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+/// Writes the play into directory as <stem>_script.txt, <stem>_<n>_config.txt for each fragment and
+/// <Name>_<stem>_<n>.txt for each part, so that the script can be run from that directory
+pub fn write_layout(play: &Play, directory: &str, stem: &str) -> Result<(), u8> {
+    let write_file = |filename: &str, contents: &str| -> Result<(), u8> {
+        let path = Path::new(directory).join(filename);
+        let result = File::create(&path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            out.write_all(contents.as_bytes())?;
+            out.flush()
+        });
+        if let Err(e) = result {
+            writeln!(std::io::stderr().lock(), "Error: Failed to write '{}': {}", path.display(), e)
+                .expect("Failed to write to stderr");
+            return Err(FAILED_TO_WRITE_OUTPUT);
+        }
+        Ok(())
+    };
+
+    if let Err(e) = fs::create_dir_all(directory) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to create directory '{}': {}", directory, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_WRITE_OUTPUT);
+    }
+
+    let mut script = String::new();
+    for (i, fragment_arc) in play.fragments().iter().enumerate() {
+        let fragment = match fragment_arc.lock() {
+            Ok(fragment) => fragment,
+            Err(_) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
+                    .expect("Failed to write to stderr");
+                return Err(SCRIPT_PARSING_ERROR);
+            }
+        };
+        let scene_stem = format!("{}_{}", stem, i + 1);
+        let config_filename = format!("{}{}", scene_stem, CONFIG_FILE_SUFFIX);
+        if fragment.has_title() {
+            script.push_str(&format!("[scene] {}\n", fragment.title()));
+        }
+        script.push_str(&format!("{}\n", config_filename));
+
+        let mut config = String::new();
+        for player_arc in fragment.players() {
+            let player = match player_arc.lock() {
+                Ok(player) => player,
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock player in fragment {}", i)
+                        .expect("Failed to write to stderr");
+                    return Err(SCRIPT_PARSING_ERROR);
+                }
+            };
+            let part_filename = format!("{}_{}{}", file_token(player.name()), scene_stem, PART_FILE_EXTENSION);
+            config.push_str(&format!("{} {}\n", part_token(player.name()), part_filename));
+
+            let pauses = player.pauses();
+            let mut part = String::new();
+            for (line_number, text) in player.lines() {
+                part.push_str(&format!("{} {}", line_number, text));
+                if let Some((_, seconds)) = pauses.iter().find(|(line, _)| line == line_number) {
+                    part.push_str(&format!(" [pause {}]", seconds));
+                }
+                part.push('\n');
+            }
+            write_file(&part_filename, &part)?;
+        }
+        write_file(&config_filename, &config)?;
+    }
+    write_file(&format!("{}{}", stem, SCRIPT_FILE_SUFFIX), &script)
+}
+
+/// Converts the source document into the project layout inside directory, choosing the
+/// importer from the source's extension
+pub fn import(source: &String, directory: &str) -> Result<(), u8> {
+    let path = Path::new(source);
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let stem = file_token(&path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default());
+
+    let play = match extension.as_str() {
+        FOUNTAIN_EXTENSION => parse_fountain(&read_source(source)?, &stem),
+        TEI_EXTENSION | XML_EXTENSION => parse_tei(&read_source(source)?, &stem),
+        TEXT_EXTENSION | TRANSCRIPT_EXTENSION => parse_transcript(&read_source(source)?, &stem),
+        CSV_EXTENSION => parse_sheet(&read_source(source)?, CSV_DELIMITER, &stem),
+        TSV_EXTENSION => parse_sheet(&read_source(source)?, TSV_DELIMITER, &stem),
+        _ => {
+            writeln!(std::io::stderr().lock(), "Error: Cannot import '{}' (expected one of: .{})",
+                     source, IMPORT_EXTENSIONS.join(", .")).expect("Failed to write to stderr");
+            return Err(BAD_COMMAND_LINE_ERROR);
+        }
+    };
+    let play = match play {
+        Ok(play) => play,
+        Err(message) => {
+            writeln!(std::io::stderr().lock(), "Error: Failed to import '{}': {}", source, message)
+                .expect("Failed to write to stderr");
+            return Err(SCRIPT_PARSING_ERROR);
+        }
+    };
+    play.check_fragments()?;
+    write_layout(&play, directory, &stem)
+}
@@ -0,0 +1,401 @@
+/// json.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A small JSON value type with a writer and a parser, enough to exchange the play model with
+/// tools written in other languages without any external crates.
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+const INDENT: &str = "  ";
+const MAX_DEPTH: usize = 256;   // arrays and objects nested deeper than this are refused rather than overflow the stack
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),   // keeps the key order it was written or read in
+}
+
+impl JsonValue {
+    /// Looks up a key in an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Non-negative whole numbers, such as line numbers
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            JsonValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    /// Writes the value as indented JSON; arrays of scalars stay on one line
+    pub fn write(&self, out: &mut dyn Write, depth: usize) -> std::io::Result<()> {
+        match self {
+            JsonValue::Array(items) if items.iter().all(|i| !matches!(i, JsonValue::Array(_) | JsonValue::Object(_))) => {
+                let rendered: Vec<String> = items.iter().map(|item| item.to_compact_string()).collect();
+                write!(out, "[{}]", rendered.join(", "))
+            }
+            JsonValue::Array(items) => {
+                writeln!(out, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(out, "{}", INDENT.repeat(depth + 1))?;
+                    item.write(out, depth + 1)?;
+                    writeln!(out, "{}", if i + 1 < items.len() { "," } else { "" })?;
+                }
+                write!(out, "{}]", INDENT.repeat(depth))
+            }
+            JsonValue::Object(members) if members.is_empty() => write!(out, "{{}}"),
+            JsonValue::Object(members) => {
+                writeln!(out, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    write!(out, "{}{}: ", INDENT.repeat(depth + 1), quote(key))?;
+                    value.write(out, depth + 1)?;
+                    writeln!(out, "{}", if i + 1 < members.len() { "," } else { "" })?;
+                }
+                write!(out, "{}}}", INDENT.repeat(depth))
+            }
+            _ => write!(out, "{}", self.to_compact_string()),
+        }
+    }
+
+    /// Renders the value on a single line
+    pub fn to_compact_string(&self) -> String {
+        match self {
+            JsonValue::Null => "null".to_string(),
+            JsonValue::Bool(b) => b.to_string(),
+            JsonValue::Number(n) => n.to_string(),
+            JsonValue::String(s) => quote(s),
+            JsonValue::Array(items) => {
+                let rendered: Vec<String> = items.iter().map(|item| item.to_compact_string()).collect();
+                format!("[{}]", rendered.join(", "))
+            }
+            JsonValue::Object(members) => {
+                let rendered: Vec<String> = members.iter()
+                    .map(|(key, value)| format!("{}: {}", quote(key), value.to_compact_string()))
+                    .collect();
+                format!("{{{}}}", rendered.join(", "))
+            }
+        }
+    }
+
+    /// Parses a complete JSON document
+    pub fn parse(text: &str) -> Result<JsonValue, String> {
+        let mut parser = Parser { chars: text.chars().collect(), position: 0, depth: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+}
+
+/// Quotes and escapes a string
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize,   // arrays and objects open at the current position
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at character {}", message, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+        let end = self.position + word.chars().count();
+        if end <= self.chars.len() && self.chars[self.position..end].iter().copied().eq(word.chars()) {
+            self.position = end;
+            Ok(value)
+        } else {
+            Err(self.error("unexpected token"))
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => Ok(JsonValue::String(self.string()?)),
+            Some('t') => self.keyword("true", JsonValue::Bool(true)),
+            Some('f') => self.keyword("false", JsonValue::Bool(false)),
+            Some('n') => self.keyword("null", JsonValue::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    /// Parses an array or object one level deeper, refusing to go past MAX_DEPTH
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<JsonValue, String>) -> Result<JsonValue, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<JsonValue, String> {
+        self.expect('{')?;
+        let mut members: Vec<(String, JsonValue)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, String> {
+        self.expect('[')?;
+        let mut items: Vec<JsonValue> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            return Err(self.error("expected a string"));
+        }
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(e) => e,
+                        None => return Err(self.error("unterminated escape")),
+                    };
+                    self.position += 1;
+                    match escaped {
+                        '"' => text.push('"'),
+                        '\\' => text.push('\\'),
+                        '/' => text.push('/'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'u' => text.push(self.unicode_escape()?),
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    /// Reads the hex digits of a \u escape, combining surrogate pairs: a high surrogate must be followed by
+    /// an escaped low surrogate, and a low surrogate cannot stand on its own
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let first = self.hex4()?;
+        if (0xDC00..0xE000).contains(&first) {
+            return Err(self.error("unpaired surrogate"));
+        }
+        if (0xD800..0xDC00).contains(&first) {
+            if self.peek() == Some('\\') && self.chars.get(self.position + 1) == Some(&'u') {
+                self.position += 2;
+                let second = self.hex4()?;
+                if !(0xDC00..0xE000).contains(&second) {
+                    return Err(self.error("invalid surrogate pair"));
+                }
+                let combined = 0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00);
+                return char::from_u32(combined).ok_or_else(|| self.error("invalid surrogate pair"));
+            }
+            return Err(self.error("unpaired surrogate"));
+        }
+        char::from_u32(first).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let end = self.position + 4;
+        if end > self.chars.len() {
+            return Err(self.error("short unicode escape"));
+        }
+        let digits: String = self.chars[self.position..end].iter().collect();
+        self.position = end;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("invalid unicode escape"));
+        }
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error("invalid unicode escape"))
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || "+-.eE".contains(c) {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse::<f64>().map(JsonValue::Number).map_err(|_| self.error("invalid number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::play::Play;
+    use super::super::model::{play_to_json, play_from_json};
+
+    const NESTED_BUNDLE: &str = "test/test_15/nested.play";
+
+    fn written(value: &JsonValue) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        value.write(&mut out, 0).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let value = JsonValue::Object(vec![
+            ("text".to_string(), JsonValue::String("\"Quoted\" \\ tab\t, newline\n, bell\u{7} and \u{1F3AD}".to_string())),
+            ("numbers".to_string(), JsonValue::Array(vec![JsonValue::Number(0.0), JsonValue::Number(-2.5),
+                                                         JsonValue::Number(1e-7), JsonValue::Number(12345.0)])),
+            ("nested".to_string(), JsonValue::Array(vec![JsonValue::Object(vec![]), JsonValue::Null,
+                                                        JsonValue::Array(vec![JsonValue::Bool(true)])])),
+            ("".to_string(), JsonValue::Bool(false)),
+        ]);
+        assert_eq!(JsonValue::parse(&written(&value)), Ok(value.clone()));
+        assert_eq!(JsonValue::parse(&value.to_compact_string()), Ok(value));
+    }
+
+    #[test]
+    fn play_model_round_trips() {
+        let mut play = Play::new();
+        play.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        let model = play_to_json(&play).unwrap();
+        let reread = play_from_json(&JsonValue::parse(&written(&model)).unwrap()).unwrap();
+        assert_eq!(play_to_json(&reread).unwrap(), model);
+    }
+
+    fn parse_error(text: &str) -> String {
+        JsonValue::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn surrogates_must_pair_up() {
+        assert_eq!(JsonValue::parse(r#""\ud83c\udfad""#), Ok(JsonValue::String("\u{1F3AD}".to_string())));
+        assert!(parse_error(r#""\ud83c\u0041""#).starts_with("invalid surrogate pair"));
+        assert!(parse_error(r#""\ud83c\ud83c""#).starts_with("invalid surrogate pair"));
+        assert!(parse_error(r#""\udfad""#).starts_with("unpaired surrogate"));
+        assert!(parse_error(r#""\ud83c x""#).starts_with("unpaired surrogate"));
+        assert!(parse_error(r#""\u+041""#).starts_with("invalid unicode escape"));
+    }
+
+    #[test]
+    fn deep_nesting_is_refused() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(JsonValue::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse_error(&nested(MAX_DEPTH + 1)).starts_with("nesting too deep"));
+        assert!(parse_error(&"[".repeat(200000)).starts_with("nesting too deep"));
+        assert!(parse_error(&"{\"a\": ".repeat(200000)).starts_with("nesting too deep"));
+    }
+}
@@ -0,0 +1,139 @@
+/// latex.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Renders a recitation as a LaTeX document. Every fragment is a section, every speaker change a
+/// \speaker macro and every enter or exit an \enter or \exit stage direction; each line carries its number
+/// in a \linenumber margin note. The macros are defined with \providecommand, so a drama package loaded in
+/// their place (or a preamble edited by hand) restyles the whole text.
+use std::io::Write;
+use super::stage::ScriptEvent;
+
+const PREAMBLE: &str = "\\documentclass{article}
+\\usepackage[utf8]{inputenc}
+\\usepackage[T1]{fontenc}
+\\providecommand{\\speaker}[1]{\\par\\medskip\\noindent\\textsc{#1}.\\par\\nopagebreak}
+\\providecommand{\\enter}[1]{\\par\\smallskip{\\centering\\textit{Enter #1.}\\par}}
+\\providecommand{\\exit}[1]{\\par\\smallskip{\\centering\\textit{Exit #1.}\\par}}
+\\providecommand{\\linenumber}[1]{\\ifnum\\numexpr#1-(#1/5)*5\\relax=0 \\leavevmode\\marginpar{\\footnotesize #1}\\fi}
+\\setlength{\\parindent}{0pt}
+";
+
+/// Escapes the characters LaTeX gives special meanings
+pub fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes the recitation as a LaTeX document titled after the first fragment
+pub fn write_latex(events: &[ScriptEvent], out: &mut dyn Write) -> std::io::Result<()> {
+    let play_title = events.iter().find_map(|event| match event {
+        ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => Some(title.clone()),
+        _ => None,
+    }).unwrap_or_default();
+
+    write!(out, "{}", PREAMBLE)?;
+    writeln!(out, "\\title{{{}}}", escape_latex(&play_title))?;
+    writeln!(out, "\\date{{}}")?;
+    writeln!(out)?;
+    writeln!(out, "\\begin{{document}}")?;
+    writeln!(out, "\\maketitle")?;
+
+    let mut in_speech = false;
+    let mut speech_lines: usize = 0;
+    for event in events {
+        // Lines of a speech are separated by forced breaks, and the speech ends its paragraph
+        if in_speech && !matches!(event, ScriptEvent::Line(_, _) | ScriptEvent::Pause(_)) {
+            writeln!(out)?;
+            writeln!(out)?;
+            in_speech = false;
+        }
+        match event {
+            ScriptEvent::Fragment(_, title) => {
+                writeln!(out)?;
+                if title.trim().is_empty() {
+                    writeln!(out, "\\section*{{}}")?;
+                } else {
+                    writeln!(out, "\\section*{{{}}}", escape_latex(title))?;
+                    writeln!(out, "\\addcontentsline{{toc}}{{section}}{{{}}}", escape_latex(title))?;
+                }
+                writeln!(out)?;
+            }
+            ScriptEvent::Enter(name) => writeln!(out, "\\enter{{{}}}", escape_latex(name))?,
+            ScriptEvent::Exit(name) => writeln!(out, "\\exit{{{}}}", escape_latex(name))?,
+            ScriptEvent::Speaker(name) => {
+                writeln!(out, "\\speaker{{{}}}", escape_latex(name))?;
+                in_speech = true;
+                speech_lines = 0;
+            }
+            ScriptEvent::Line(line_number, text) => {
+                if speech_lines > 0 {
+                    writeln!(out, "\\\\")?;
+                }
+                write!(out, "\\linenumber{{{}}}{}", line_number, escape_latex(text))?;
+                speech_lines += 1;
+            }
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    if in_speech {
+        writeln!(out)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "\\end{{document}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latex(events: &[ScriptEvent]) -> String {
+        let mut out = Vec::new();
+        write_latex(events, &mut out).expect("Failed to write to a vector");
+        String::from_utf8(out).expect("LaTeX output is not UTF-8")
+    }
+
+    // \numexpr divides with rounding to the nearest integer, not truncation
+    fn numexpr_divide(numerator: i64, denominator: i64) -> i64 {
+        (2 * numerator + denominator).div_euclid(2 * denominator)
+    }
+
+    #[test]
+    fn every_line_carries_its_number() {
+        let events = vec![
+            ScriptEvent::Fragment(0, "ACT I Scene I".to_string()),
+            ScriptEvent::Speaker("Bernardo".to_string()),
+            ScriptEvent::Line(1, "Who's there?".to_string()),
+            ScriptEvent::Speaker("Francisco".to_string()),
+            ScriptEvent::Line(2, "Nay, answer me: stand, & unfold yourself.".to_string()),
+            ScriptEvent::Line(3, "Long live the king!".to_string()),
+        ];
+        let text = latex(&events);
+        assert!(text.contains("\\speaker{Bernardo}\n\\linenumber{1}Who's there?\n"));
+        assert!(text.contains("\\linenumber{2}Nay, answer me: stand, \\& unfold yourself.\\\\\n\\linenumber{3}Long live"));
+    }
+
+    #[test]
+    fn the_macro_shows_every_fifth_number() {
+        let text = latex(&[]);
+        let definition = text.lines().find(|line| line.starts_with("\\providecommand{\\linenumber}"))
+            .expect("The preamble does not define \\linenumber");
+        assert!(definition.contains("\\ifnum\\numexpr#1-(#1/5)*5\\relax=0 "));
+        assert!(definition.contains("\\marginpar{\\footnotesize #1}"));
+
+        // the same test the macro makes, with TeX's rounding division
+        let shown: Vec<i64> = (1..=20).filter(|n| n - numexpr_divide(*n, 5) * 5 == 0).collect();
+        assert_eq!(shown, vec![5, 10, 15, 20]);
+    }
+}
@@ -0,0 +1,115 @@
+/// markup.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Renders a recitation as Markdown or reStructuredText. Fragment titles become headings, speaker
+/// changes become bold names and enter/exit directives become emphasized lines. Line numbers can optionally
+/// be emitted as anchors.
+use std::io::Write;
+use super::stage::ScriptEvent;
+use super::html::line_anchor;
+
+const MARKDOWN_SPECIAL: &str = "\\`*_[]<>#|";
+const RST_SPECIAL: &str = "\\`*_|";
+const MARKDOWN_HARD_BREAK: &str = "  ";
+const RST_TITLE_UNDERLINE: char = '=';
+
+fn escape(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn escape_markdown(text: &str) -> String {
+    escape(text, MARKDOWN_SPECIAL)
+}
+
+pub fn escape_rst(text: &str) -> String {
+    escape(text, RST_SPECIAL)
+}
+
+/// Writes the recitation as Markdown
+pub fn write_markdown(events: &[ScriptEvent], line_anchors: bool, out: &mut dyn Write) -> std::io::Result<()> {
+    let mut fragment_index: usize = 0;
+    let mut in_speech = false;
+    for event in events {
+        if in_speech && !matches!(event, ScriptEvent::Line(_, _) | ScriptEvent::Pause(_)) {
+            writeln!(out)?;
+            in_speech = false;
+        }
+        match event {
+            ScriptEvent::Fragment(index, title) => {
+                fragment_index = *index;
+                if !title.trim().is_empty() {
+                    writeln!(out, "## {}", escape_markdown(title))?;
+                    writeln!(out)?;
+                }
+            }
+            ScriptEvent::Enter(name) => writeln!(out, "*\\[Enter {}.\\]*\n", escape_markdown(name))?,
+            ScriptEvent::Exit(name) => writeln!(out, "*\\[Exit {}.\\]*\n", escape_markdown(name))?,
+            ScriptEvent::Speaker(name) => {
+                writeln!(out, "**{}.**{}", escape_markdown(name), MARKDOWN_HARD_BREAK)?;
+                in_speech = true;
+            }
+            ScriptEvent::Line(line_number, text) => {
+                if line_anchors {
+                    write!(out, "<a id=\"{}\"></a>", line_anchor(fragment_index, *line_number))?;
+                }
+                writeln!(out, "{}{}", escape_markdown(text), MARKDOWN_HARD_BREAK)?;
+            }
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    Ok(())
+}
+
+/// Writes the recitation as reStructuredText. Speeches are line blocks so that verse keeps its lines;
+/// with anchors, every line becomes its own labelled line block.
+pub fn write_rst(events: &[ScriptEvent], line_anchors: bool, out: &mut dyn Write) -> std::io::Result<()> {
+    let mut fragment_index: usize = 0;
+    let mut in_speech = false;
+    let mut speech_lines: usize = 0;
+    for event in events {
+        if in_speech && !matches!(event, ScriptEvent::Line(_, _) | ScriptEvent::Pause(_)) {
+            writeln!(out)?;
+            in_speech = false;
+        }
+        match event {
+            ScriptEvent::Fragment(index, title) => {
+                fragment_index = *index;
+                if !title.trim().is_empty() {
+                    let heading = escape_rst(title);
+                    writeln!(out, "{}", heading)?;
+                    writeln!(out, "{}", RST_TITLE_UNDERLINE.to_string().repeat(heading.chars().count()))?;
+                    writeln!(out)?;
+                }
+            }
+            ScriptEvent::Enter(name) => writeln!(out, "*[Enter {}.]*\n", escape_rst(name))?,
+            ScriptEvent::Exit(name) => writeln!(out, "*[Exit {}.]*\n", escape_rst(name))?,
+            ScriptEvent::Speaker(name) => {
+                writeln!(out, "**{}.**", escape_rst(name))?;
+                writeln!(out)?;
+                in_speech = true;
+                speech_lines = 0;
+            }
+            ScriptEvent::Line(line_number, text) => {
+                if line_anchors {
+                    if speech_lines > 0 {
+                        writeln!(out)?;
+                    }
+                    writeln!(out, ".. _{}:", line_anchor(fragment_index, *line_number))?;
+                    writeln!(out)?;
+                    writeln!(out, "| {}", escape_rst(text))?;
+                } else {
+                    writeln!(out, "| {}", escape_rst(text))?;
+                }
+                speech_lines += 1;
+            }
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    Ok(())
+}
@@ -6,4 +6,38 @@ pub mod script_gen;
 pub mod play;
 pub mod player;
 pub mod return_wrapper;
-pub mod scene_fragment;
\ No newline at end of file
+pub mod scene_fragment;
+pub mod stage;
+pub mod director;
+pub mod actor;
+pub mod broadcast;
+pub mod pace;
+pub mod options;
+pub mod rehearsal;
+pub mod scoring;
+pub mod sides;
+pub mod export;
+pub mod html;
+pub mod markup;
+pub mod json;
+pub mod model;
+pub mod fountain;
+pub mod import;
+pub mod xml;
+pub mod tei;
+pub mod transcript;
+pub mod bundle;
+pub use lab3tar as tar;
+pub mod zip;
+pub mod epub;
+pub mod pdf;
+pub mod latex;
+pub mod subtitles;
+pub mod ssml;
+pub mod sheet;
+pub mod terminal;
+pub mod regex;
+pub mod pager;
+pub mod step;
+pub mod numbering;
+pub mod selection;
@@ -0,0 +1,156 @@
+/// model.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Dumps the play model built by Play::prepare as JSON, and rebuilds a Play from such a dump. The JSON
+/// holds every fragment with its title, the entrances and exits around it, and its players with their source
+/// files, numbered lines and pauses.
+use std::io::{Read, Write};
+use super::json::JsonValue;
+use super::play::Play;
+use super::player::{Player, PlayLines};
+use super::scene_fragment::SceneFragment;
+use super::pace::is_valid_pause;
+use super::script_gen::get_buffered_reader;
+use super::declarations::{SCRIPT_PARSING_ERROR, FAILED_TO_READ_LINE_FROM_FILE};
+
+pub const MODEL_FILE_EXTENSION: &str = ".json";
+pub const MODEL_FORMAT_NAME: &str = "lab3-play";
+pub const MODEL_VERSION: usize = 1;
+
+/// Script files ending in .json are loaded as a play model instead of a script configuration
+pub fn is_model_file(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(MODEL_FILE_EXTENSION)
+}
+
+fn string_array(names: &[String]) -> JsonValue {
+    JsonValue::Array(names.iter().map(|name| JsonValue::String(name.clone())).collect())
+}
+
+fn player_to_json(player: &Player) -> JsonValue {
+    let lines = player.lines().iter()
+        .map(|(line_number, text)| JsonValue::Array(vec![
+            JsonValue::Number(*line_number as f64),
+            JsonValue::String(text.clone()),
+        ]))
+        .collect();
+    let pauses = player.pauses().iter()
+        .map(|(line_number, seconds)| JsonValue::Array(vec![
+            JsonValue::Number(*line_number as f64),
+            JsonValue::Number(*seconds),
+        ]))
+        .collect();
+    JsonValue::Object(vec![
+        ("name".to_string(), JsonValue::String(player.name().clone())),
+        ("source".to_string(), JsonValue::String(player.source().clone())),
+        ("lines".to_string(), JsonValue::Array(lines)),
+        ("pauses".to_string(), JsonValue::Array(pauses)),
+    ])
+}
+
+/// Builds the JSON model of a prepared play
+pub fn play_to_json(play: &Play) -> Result<JsonValue, u8> {
+    let changes = play.cast_changes()?;
+    let mut fragments: Vec<JsonValue> = Vec::new();
+
+    for (index, (fragment_arc, (entering, exiting))) in play.fragments().iter().zip(changes.iter()).enumerate() {
+        let fragment = match fragment_arc.lock() {
+            Ok(fragment) => fragment,
+            Err(_) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", index)
+                    .expect("Failed to write to stderr");
+                return Err(SCRIPT_PARSING_ERROR);
+            }
+        };
+        let mut players: Vec<JsonValue> = Vec::new();
+        for player_arc in fragment.players() {
+            match player_arc.lock() {
+                Ok(ref player) => players.push(player_to_json(player)),
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock player in fragment {}", index)
+                        .expect("Failed to write to stderr");
+                    return Err(SCRIPT_PARSING_ERROR);
+                }
+            }
+        }
+        fragments.push(JsonValue::Object(vec![
+            ("index".to_string(), JsonValue::Number(index as f64)),
+            ("title".to_string(), JsonValue::String(fragment.title().clone())),
+            ("enter".to_string(), string_array(entering)),
+            ("exit".to_string(), string_array(exiting)),
+            ("players".to_string(), JsonValue::Array(players)),
+        ]));
+    }
+
+    Ok(JsonValue::Object(vec![
+        ("format".to_string(), JsonValue::String(MODEL_FORMAT_NAME.to_string())),
+        ("version".to_string(), JsonValue::Number(MODEL_VERSION as f64)),
+        ("fragments".to_string(), JsonValue::Array(fragments)),
+    ]))
+}
+
+fn model_error(filename: &str, message: &str) -> u8 {
+    writeln!(std::io::stderr().lock(), "Error: Invalid play model '{}': {}", filename, message)
+        .expect("Failed to write to stderr");
+    SCRIPT_PARSING_ERROR
+}
+
+fn player_from_json(value: &JsonValue) -> Result<Player, String> {
+    let name = value.get("name").and_then(JsonValue::as_str).ok_or("player without a name")?;
+    let mut lines: PlayLines = Vec::new();
+    for line in value.get("lines").and_then(JsonValue::as_array).ok_or("player without lines")? {
+        match line.as_array().map(|pair| pair.as_slice()) {
+            Some([number, text]) => {
+                let number = number.as_usize().ok_or("line number is not a whole number")?;
+                let text = text.as_str().ok_or("line text is not a string")?;
+                lines.push((number, text.to_string()));
+            }
+            _ => return Err(format!("line of '{}' is not a [line_number, text] pair", name)),
+        }
+    }
+
+    let mut player = Player::from_lines(&name.to_string(), lines);
+    if let Some(source) = value.get("source").and_then(JsonValue::as_str) {
+        player.set_source(source);
+    }
+    // pauses are optional so that other tools can omit them
+    if let Some(pauses) = value.get("pauses").and_then(JsonValue::as_array) {
+        for pause in pauses {
+            match pause.as_array().map(|pair| pair.as_slice()) {
+                Some([number, seconds]) => match (number.as_usize(), seconds.as_f64()) {
+                    (Some(number), Some(seconds)) if is_valid_pause(seconds) => player.add_pause(number, seconds),
+                    _ => return Err(format!("invalid pause for '{}'", name)),
+                },
+                _ => return Err(format!("pause of '{}' is not a [line_number, seconds] pair", name)),
+            }
+        }
+    }
+    Ok(player)
+}
+
+/// Rebuilds a play from its JSON model. Entrances and exits are recomputed from the players, so the
+/// "enter" and "exit" members are not read.
+pub fn play_from_json(value: &JsonValue) -> Result<Play, String> {
+    let mut fragments: Vec<SceneFragment> = Vec::new();
+    for fragment in value.get("fragments").and_then(JsonValue::as_array).ok_or("no fragments array")? {
+        let title = fragment.get("title").and_then(JsonValue::as_str).unwrap_or_default();
+        let mut players: Vec<Player> = Vec::new();
+        for player in fragment.get("players").and_then(JsonValue::as_array).ok_or("fragment without players")? {
+            players.push(player_from_json(player)?);
+        }
+        fragments.push(SceneFragment::from_players(&title.to_string(), players));
+    }
+    Ok(Play::from_fragments(fragments))
+}
+
+/// Reads a JSON model from a file (or any source get_buffered_reader accepts)
+pub fn load_play_json(filename: &String) -> Result<Play, u8> {
+    let mut reader = get_buffered_reader(filename)?;
+    let mut text = String::new();
+    if let Err(e) = reader.read_to_string(&mut text) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to read '{}': {}", filename, e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_READ_LINE_FROM_FILE);
+    }
+
+    let value = JsonValue::parse(&text).map_err(|message| model_error(filename, &message))?;
+    play_from_json(&value).map_err(|message| model_error(filename, &message))
+}
@@ -0,0 +1,197 @@
+/// numbering.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Labels recited lines in the margin of the text, HTML and PDF outputs: every line or every Nth line,
+/// with its line number or with an act.scene.line citation. Citations take the act and scene from a fragment's
+/// title when it names them ("ACT II Scene I", "Act 3 Scene 2"); otherwise the fragment's position decides. A
+/// titled fragment is the next scene of the current act, and an untitled fragment continues the scene before
+/// it, so its lines are cited on from where that scene's left off. A fragment cut out of a larger play carries
+/// its place in a Citation event, so a selection is cited just as the whole play is.
+use super::stage::ScriptEvent;
+
+pub type Citation = (usize, usize, usize);  // (act, scene, line_offset)
+
+pub const ACT_WORD: &str = "act";
+pub const SCENE_WORD: &str = "scene";
+const NUMBER_MARGIN_WIDTH: usize = 4;
+const CITATION_MARGIN_WIDTH: usize = 9;
+const MARGIN_GAP: &str = "  ";
+const ROMAN_DIGITS: [(char, usize); 7] = [('i', 1), ('v', 5), ('x', 10), ('l', 50), ('c', 100), ('d', 500), ('m', 1000)];
+
+/// Which lines get a margin label, and whether the label is the line number or a citation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Numbering {
+    pub every: usize,       // label every line whose number is a multiple of this; 0 labels none
+    pub citations: bool,    // label with act.scene.line instead of the line number
+}
+
+impl Numbering {
+    pub fn is_on(&self) -> bool {
+        self.every > 0
+    }
+}
+
+/// Parses an arabic or (case-insensitive) roman numeral
+pub fn parse_numeral(text: &str) -> Option<usize> {
+    if let Ok(number) = text.parse::<usize>() {
+        return Some(number);
+    }
+    let values: Vec<usize> = text.to_lowercase().chars()
+        .map(|c| ROMAN_DIGITS.iter().find(|(digit, _)| *digit == c).map(|(_, value)| *value))
+        .collect::<Option<Vec<usize>>>()?;
+    if values.is_empty() {
+        return None;
+    }
+    // a digit before a larger one is subtracted, as in IV and XC
+    let total = values.iter().enumerate().fold(0isize, |total, (i, value)| {
+        match values.get(i + 1) {
+            Some(next) if next > value => total - *value as isize,
+            _ => total + *value as isize,
+        }
+    });
+    usize::try_from(total).ok().filter(|total| *total > 0)
+}
+
+/// The number following a word in a title, as in "ACT II" or "Scene 3"
+fn number_after(title: &str, word: &str) -> Option<usize> {
+    let words: Vec<String> = title.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.windows(2).find(|pair| pair[0] == word).and_then(|pair| parse_numeral(&pair[1]))
+}
+
+/// Follows a recitation's fragments to label its lines
+pub struct LineLabels {
+    numbering: Numbering,
+    act: usize,
+    scene: usize,           // 0 before the first scene of an act
+    offset: usize,          // lines of the scene in earlier fragments, for untitled continuations
+    last_line: usize,       // highest line number seen in the current fragment
+}
+
+impl LineLabels {
+    pub fn new(numbering: Numbering) -> LineLabels {
+        LineLabels { numbering, act: 1, scene: 0, offset: 0, last_line: 0 }
+    }
+
+    /// Follows fragment changes and line numbers; every event of the recitation goes through here in order
+    pub fn observe(&mut self, event: &ScriptEvent) {
+        match event {
+            ScriptEvent::Fragment(_, title) if title.trim().is_empty() => {
+                self.offset += self.last_line;
+                self.scene = self.scene.max(1);
+                self.last_line = 0;
+            }
+            ScriptEvent::Fragment(_, title) => {
+                if let Some(act) = number_after(title, ACT_WORD) {
+                    if act != self.act {
+                        self.act = act;
+                        self.scene = 0;
+                    }
+                }
+                self.scene = number_after(title, SCENE_WORD).unwrap_or(self.scene + 1);
+                self.offset = 0;
+                self.last_line = 0;
+            }
+            ScriptEvent::Citation(act, scene, offset) => {
+                self.act = *act;
+                self.scene = *scene;
+                self.offset = *offset;
+            }
+            ScriptEvent::Line(line_number, _) => self.last_line = self.last_line.max(*line_number),
+            _ => {}
+        }
+    }
+
+    /// The act, scene and line offset that the current fragment's lines are cited with
+    pub fn citation(&self) -> Citation {
+        (self.act, self.scene, self.offset)
+    }
+
+    /// The label for a line of the current fragment, or None if the line goes unlabelled
+    pub fn label(&self, line_number: usize) -> Option<String> {
+        if !self.numbering.is_on() {
+            return None;
+        }
+        let number = if self.numbering.citations { self.offset + line_number } else { line_number };
+        if number % self.numbering.every != 0 {
+            return None;
+        }
+        if self.numbering.citations {
+            Some(format!("{}.{}.{}", self.act, self.scene, number))
+        } else {
+            Some(number.to_string())
+        }
+    }
+
+    /// The label right aligned in a fixed-width text margin (blank for unlabelled lines), then a gap
+    pub fn margin(&self, line_number: usize) -> String {
+        let width = if self.numbering.citations { CITATION_MARGIN_WIDTH } else { NUMBER_MARGIN_WIDTH };
+        format!("{:>width$}{}", self.label(line_number).unwrap_or_default(), MARGIN_GAP, width = width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first scene of Act II runs to line 60 in its titled fragment, then on in an untitled one
+    fn through_continuation(numbering: Numbering) -> LineLabels {
+        let mut labels = LineLabels::new(numbering);
+        labels.observe(&ScriptEvent::Fragment(0, "Hamlet ACT II Scene I A room in Polonius' house".to_string()));
+        for line_number in 1..=60 {
+            labels.observe(&ScriptEvent::Line(line_number, String::new()));
+        }
+        labels.observe(&ScriptEvent::Fragment(1, String::new()));
+        labels
+    }
+
+    #[test]
+    fn every_line_is_labelled() {
+        let labels = LineLabels::new(Numbering { every: 1, citations: false });
+        assert_eq!(labels.label(1), Some("1".to_string()));
+        assert_eq!(labels.label(4), Some("4".to_string()));
+        assert_eq!(labels.margin(3), "   3  ");
+        assert_eq!(labels.margin(1234), "1234  ");
+    }
+
+    #[test]
+    fn every_fifth_line_is_labelled() {
+        let labels = LineLabels::new(Numbering { every: 5, citations: false });
+        let labelled: Vec<usize> = (1..=20).filter(|n| labels.label(*n).is_some()).collect();
+        assert_eq!(labelled, vec![5, 10, 15, 20]);
+        assert_eq!(labels.margin(4), "      ");
+        assert_eq!(labels.margin(10), "  10  ");
+    }
+
+    #[test]
+    fn numbering_off_labels_nothing() {
+        let labels = LineLabels::new(Numbering::default());
+        assert_eq!(labels.label(5), None);
+        assert_eq!(labels.margin(5), "      ");
+    }
+
+    #[test]
+    fn citations_continue_across_untitled_fragments() {
+        let labels = through_continuation(Numbering { every: 1, citations: true });
+        assert_eq!(labels.citation(), (2, 1, 60));
+        assert_eq!(labels.label(1), Some("2.1.61".to_string()));
+        assert_eq!(labels.margin(5), "   2.1.65  ");
+
+        // every fifth line counts the scene's lines, not the fragment's
+        let labels = through_continuation(Numbering { every: 5, citations: true });
+        assert_eq!(labels.label(4), None);
+        assert_eq!(labels.label(5), Some("2.1.65".to_string()));
+        assert_eq!(labels.margin(4), "           ");
+    }
+
+    #[test]
+    fn titled_fragments_start_the_next_scene() {
+        let mut labels = through_continuation(Numbering { every: 1, citations: true });
+        labels.observe(&ScriptEvent::Line(12, String::new()));
+        labels.observe(&ScriptEvent::Fragment(2, "A room in the castle".to_string()));
+        assert_eq!(labels.citation(), (2, 2, 0));
+        labels.observe(&ScriptEvent::Fragment(3, "Act 3 Scene 1".to_string()));
+        assert_eq!(labels.label(7), Some("3.1.7".to_string()));
+    }
+}
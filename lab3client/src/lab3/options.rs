@@ -0,0 +1,217 @@
+/// options.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Parses the optional --flag arguments that change how a play is recited.
+use std::io::Write;
+use super::declarations::BAD_COMMAND_LINE_ERROR;
+use super::pace::{PaceSettings, is_valid_pause, MAX_PAUSE, MIN_WORDS_PER_MINUTE, MAX_WORDS_PER_MINUTE, MIN_SPEED,
+                  MAX_SPEED};
+use super::export::{is_format, FORMATS};
+use super::numbering::Numbering;
+use super::selection::{Selection, parse_line_range, parse_names};
+
+pub const OPTION_PREFIX: &str = "--";
+pub const WPM_OPTION: &str = "--wpm";
+pub const SPEAKER_PAUSE_OPTION: &str = "--speaker-pause";
+pub const SCENE_PAUSE_OPTION: &str = "--scene-pause";
+pub const DIRECTION_PAUSE_OPTION: &str = "--direction-pause";
+pub const SPEED_OPTION: &str = "--speed";
+pub const ESTIMATE_OPTION: &str = "--estimate";
+pub const AS_OPTION: &str = "--as";
+pub const SCORE_OPTION: &str = "--score";
+pub const HISTORY_OPTION: &str = "--history";
+pub const OUTPUT_OPTION: &str = "--output";
+pub const FORMAT_OPTION: &str = "--format";
+pub const LINE_ANCHORS_OPTION: &str = "--line-anchors";
+pub const CASTING_OPTION: &str = "--casting";
+pub const TERMINAL_OPTION: &str = "--terminal";
+pub const WRAP_OPTION: &str = "--wrap";
+pub const STEP_OPTION: &str = "--step";
+pub const LINE_NUMBERS_OPTION: &str = "--line-numbers";
+pub const CITATIONS_OPTION: &str = "--citations";
+pub const SCENE_OPTION: &str = "--scene";
+pub const LINES_OPTION: &str = "--lines";
+pub const ONLY_OPTION: &str = "--only";
+
+pub struct RecitalOptions {
+    pub pace: Option<PaceSettings>,
+    pub character: Option<String>,  // the part read by a human in rehearsal
+    pub score: bool,                // score typed rehearsal attempts
+    pub history: Option<String>,    // history file for rehearsal scores
+    pub output: Option<String>,     // output file or directory of commands that write files
+    pub format: Option<String>,     // export format for the recitation
+    pub line_anchors: bool,         // emit an anchor for every line in markdown and rst output
+    pub casting: Option<String>,    // casting file of voices for ssml output
+    pub terminal: bool,             // color and wrap the recitation for a terminal
+    pub wrap: Option<usize>,        // wrap column for terminal output, instead of the terminal's width
+    pub step: bool,                 // wait for a keypress after every line
+    pub numbering: Numbering,       // line numbers or citations in text, html and pdf output
+    pub selection: Selection,       // the scenes, lines and characters to recite, or all of them
+}
+
+impl Default for RecitalOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecitalOptions {
+    pub fn new() -> RecitalOptions {
+        RecitalOptions {
+            pace: None,
+            character: None,
+            score: false,
+            history: None,
+            output: None,
+            format: None,
+            line_anchors: false,
+            casting: None,
+            terminal: false,
+            wrap: None,
+            step: false,
+            numbering: Numbering::default(),
+            selection: Selection::default(),
+        }
+    }
+
+    /// Usage text for the recital options, one option per line
+    pub fn usage() -> Vec<String> {
+        vec![
+            format!("{} <words_per_minute> {} <seconds> {} <seconds> {} <seconds> {} <multiplier> {}",
+                    WPM_OPTION, SPEAKER_PAUSE_OPTION, SCENE_PAUSE_OPTION, DIRECTION_PAUSE_OPTION,
+                    SPEED_OPTION, ESTIMATE_OPTION),
+            format!("{} <character_name> [{} [{} <history_file>]]", AS_OPTION, SCORE_OPTION, HISTORY_OPTION),
+            format!("{} <file_or_directory>", OUTPUT_OPTION),
+            format!("{} <number_or_title> {} <first>-<last> {} <character_name>[,<character_name>...]",
+                    SCENE_OPTION, LINES_OPTION, ONLY_OPTION),
+            format!("{} <every_nth_line> {}", LINE_NUMBERS_OPTION, CITATIONS_OPTION),
+            format!("{} [{} <columns>] {}", TERMINAL_OPTION, WRAP_OPTION, STEP_OPTION),
+            format!("{} {} [{}] [{} <casting_file>]", FORMAT_OPTION, FORMATS.join("|"), LINE_ANCHORS_OPTION,
+                    CASTING_OPTION),
+        ]
+    }
+
+    /// Splits the command line into positional arguments and options, applying every option found
+    pub fn parse(&mut self, args: &[String], positional: &mut Vec<String>) -> Result<(), u8> {
+        let mut remaining = args.iter();
+        while let Some(arg) = remaining.next() {
+            if arg.starts_with(OPTION_PREFIX) {
+                self.apply(arg, &mut remaining)?;
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn apply<'a>(&mut self, flag: &str, remaining: &mut impl Iterator<Item = &'a String>) -> Result<(), u8> {
+        match flag {
+            WPM_OPTION => {
+                self.pace_mut().words_per_minute = Self::number_between(flag, remaining.next(), MIN_WORDS_PER_MINUTE,
+                                                                        MAX_WORDS_PER_MINUTE)?;
+            }
+            SPEAKER_PAUSE_OPTION => self.pace_mut().speaker_pause = Self::seconds(flag, remaining.next())?,
+            SCENE_PAUSE_OPTION => self.pace_mut().scene_pause = Self::seconds(flag, remaining.next())?,
+            DIRECTION_PAUSE_OPTION => self.pace_mut().direction_pause = Self::seconds(flag, remaining.next())?,
+            SPEED_OPTION => self.pace_mut().speed = Self::number_between(flag, remaining.next(), MIN_SPEED, MAX_SPEED)?,
+            ESTIMATE_OPTION => self.pace_mut().estimate_only = true,
+            AS_OPTION => self.character = Some(Self::value(flag, remaining.next())?),
+            SCORE_OPTION => self.score = true,
+            LINE_ANCHORS_OPTION => self.line_anchors = true,
+            OUTPUT_OPTION => self.output = Some(Self::value(flag, remaining.next())?),
+            CASTING_OPTION => self.casting = Some(Self::value(flag, remaining.next())?),
+            TERMINAL_OPTION => self.terminal = true,
+            STEP_OPTION => self.step = true,
+            WRAP_OPTION => {
+                self.terminal = true;
+                self.wrap = Some(Self::count(flag, remaining.next(), "columns")?);
+            }
+            LINE_NUMBERS_OPTION => self.numbering.every = Self::count(flag, remaining.next(), "lines")?,
+            CITATIONS_OPTION => {
+                self.numbering.citations = true;
+                if !self.numbering.is_on() {
+                    self.numbering.every = 1;   // cite every line unless --line-numbers says otherwise
+                }
+            }
+            SCENE_OPTION => self.selection.scenes.push(Self::value(flag, remaining.next())?),
+            LINES_OPTION => {
+                let range = Self::value(flag, remaining.next())?;
+                match parse_line_range(&range) {
+                    Some(range) => self.selection.lines = Some(range),
+                    None => {
+                        writeln!(std::io::stderr().lock(), "Error: {} expects a range of line numbers like 10-40, \
+                                 not '{}'", flag, range).expect("Failed to write to stderr");
+                        return Err(BAD_COMMAND_LINE_ERROR);
+                    }
+                }
+            }
+            ONLY_OPTION => self.selection.characters.extend(parse_names(&Self::value(flag, remaining.next())?)),
+            FORMAT_OPTION => {
+                let format = Self::value(flag, remaining.next())?;
+                if !is_format(&format) {
+                    writeln!(std::io::stderr().lock(), "Error: Unknown format '{}' (expected one of: {})",
+                             format, FORMATS.join(", ")).expect("Failed to write to stderr");
+                    return Err(BAD_COMMAND_LINE_ERROR);
+                }
+                self.format = Some(format);
+            }
+            HISTORY_OPTION => {
+                self.score = true;
+                self.history = Some(Self::value(flag, remaining.next())?);
+            }
+            _ => {
+                writeln!(std::io::stderr().lock(), "Error: Unknown option '{}'", flag).expect("Failed to write to stderr");
+                return Err(BAD_COMMAND_LINE_ERROR);
+            }
+        }
+        Ok(())
+    }
+
+    /// Any pacing option turns on paced mode with defaults for the others
+    fn pace_mut(&mut self) -> &mut PaceSettings {
+        self.pace.get_or_insert_with(PaceSettings::default)
+    }
+
+    fn value(flag: &str, value: Option<&String>) -> Result<String, u8> {
+        match value {
+            Some(v) => Ok(v.clone()),
+            None => {
+                writeln!(std::io::stderr().lock(), "Error: {} expects a value", flag).expect("Failed to write to stderr");
+                Err(BAD_COMMAND_LINE_ERROR)
+            }
+        }
+    }
+
+    fn seconds(flag: &str, value: Option<&String>) -> Result<f64, u8> {
+        match value.map(|v| v.parse::<f64>()) {
+            Some(Ok(seconds)) if is_valid_pause(seconds) => Ok(seconds),
+            _ => {
+                writeln!(std::io::stderr().lock(), "Error: {} expects a number of seconds from 0 to {}", flag, MAX_PAUSE)
+                    .expect("Failed to write to stderr");
+                Err(BAD_COMMAND_LINE_ERROR)
+            }
+        }
+    }
+
+    fn count(flag: &str, value: Option<&String>, unit: &str) -> Result<usize, u8> {
+        match value.map(|v| v.parse::<usize>()) {
+            Some(Ok(count)) if count > 0 => Ok(count),
+            _ => {
+                writeln!(std::io::stderr().lock(), "Error: {} expects a positive number of {}", flag, unit)
+                    .expect("Failed to write to stderr");
+                Err(BAD_COMMAND_LINE_ERROR)
+            }
+        }
+    }
+
+    /// A finite number within bounds; the checks keep pacing from holding a line for an impossible time
+    fn number_between(flag: &str, value: Option<&String>, min: f64, max: f64) -> Result<f64, u8> {
+        match value.map(|v| v.parse::<f64>()) {
+            Some(Ok(number)) if (min..=max).contains(&number) => Ok(number),
+            _ => {
+                writeln!(std::io::stderr().lock(), "Error: {} expects a number from {} to {}", flag, min, max)
+                    .expect("Failed to write to stderr");
+                Err(BAD_COMMAND_LINE_ERROR)
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+/// pace.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Implements a stage that paces a recitation in real time. Each line is held for as long as it takes
+/// to speak at the configured words per minute, with extra pauses at speaker changes, scene changes, after stage
+/// directions and wherever a part file annotates a line with [pause <seconds>]. The speed multiplier can be
+/// changed from stdin while the play is running.
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use super::stage::{Stage, ScriptEvent, Step};
+
+pub const DEFAULT_WORDS_PER_MINUTE: f64 = 150.0;
+pub const DEFAULT_SPEAKER_PAUSE: f64 = 0.5;    // seconds
+pub const DEFAULT_SCENE_PAUSE: f64 = 2.0;      // seconds
+pub const DEFAULT_DIRECTION_PAUSE: f64 = 1.0;  // seconds
+pub const SPEED_STEP: f64 = 1.25;              // '+' and '-' multiply or divide the speed by this
+pub const MAX_PAUSE: f64 = 3600.0;             // seconds
+pub const MIN_WORDS_PER_MINUTE: f64 = 1.0;
+pub const MAX_WORDS_PER_MINUTE: f64 = 10000.0;
+pub const MIN_SPEED: f64 = 0.01;
+pub const MAX_SPEED: f64 = 100.0;
+const SECONDS_PER_MINUTE: f64 = 60.0;
+
+/// True for a pause that can be waited out: from 0 to MAX_PAUSE seconds (never infinite or NaN)
+pub fn is_valid_pause(seconds: f64) -> bool {
+    (0.0..=MAX_PAUSE).contains(&seconds)
+}
+
+pub fn is_valid_speed(speed: f64) -> bool {
+    (MIN_SPEED..=MAX_SPEED).contains(&speed)
+}
+
+#[derive(Clone, Debug)]
+pub struct PaceSettings {
+    pub words_per_minute: f64,
+    pub speaker_pause: f64,
+    pub scene_pause: f64,
+    pub direction_pause: f64,
+    pub speed: f64,
+    pub estimate_only: bool,    // compute the running time without waiting
+}
+
+impl Default for PaceSettings {
+    fn default() -> Self {
+        PaceSettings {
+            words_per_minute: DEFAULT_WORDS_PER_MINUTE,
+            speaker_pause: DEFAULT_SPEAKER_PAUSE,
+            scene_pause: DEFAULT_SCENE_PAUSE,
+            direction_pause: DEFAULT_DIRECTION_PAUSE,
+            speed: 1.0,
+            estimate_only: false,
+        }
+    }
+}
+
+impl PaceSettings {
+    /// Seconds needed to speak a line at the configured rate (before the speed multiplier)
+    pub fn speaking_time(&self, text: &str) -> f64 {
+        text.split_whitespace().count() as f64 * SECONDS_PER_MINUTE / self.words_per_minute
+    }
+}
+
+/// Wraps another stage, forwarding every event and then waiting as long as the event takes to perform
+pub struct PacedStage<S: Stage> {
+    inner: S,
+    settings: PaceSettings,
+    speed: Arc<Mutex<f64>>,
+    running_time: f64,  // seconds at normal speed
+}
+
+impl<S: Stage> PacedStage<S> {
+    pub fn new(inner: S, settings: PaceSettings) -> PacedStage<S> {
+        let speed = Arc::new(Mutex::new(settings.speed));
+        PacedStage {
+            inner,
+            settings,
+            speed,
+            running_time: 0.0,
+        }
+    }
+
+    /// Starts a thread that reads speed changes from stdin while the play runs:
+    /// '+' speeds up, '-' slows down, and a number sets the multiplier directly
+    pub fn listen_for_speed_changes(&self) {
+        let speed = Arc::clone(&self.speed);
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let command = match line {
+                    Ok(text) => text.trim().to_string(),
+                    Err(_) => return,
+                };
+                if let Ok(ref mut current) = speed.lock() {
+                    match command.as_str() {
+                        "+" => **current = (**current * SPEED_STEP).min(MAX_SPEED),
+                        "-" => **current = (**current / SPEED_STEP).max(MIN_SPEED),
+                        _ => match command.parse::<f64>() {
+                            Ok(multiplier) if is_valid_speed(multiplier) => **current = multiplier,
+                            _ => {
+                                let _ = writeln!(std::io::stderr().lock(),
+                                                 "Warning: Speed must be '+', '-' or a number from {} to {}, got '{}'",
+                                                 MIN_SPEED, MAX_SPEED, command);
+                            }
+                        },
+                    }
+                }
+            }
+        });
+    }
+
+    /// Total time the performance takes at normal speed
+    pub fn running_time(&self) -> Duration {
+        Duration::try_from_secs_f64(self.running_time).unwrap_or(Duration::MAX)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn hold(&mut self, seconds: f64) {
+        self.running_time += seconds;
+        if self.settings.estimate_only || seconds <= 0.0 {
+            return;
+        }
+        let speed = match self.speed.lock() {
+            Ok(ref current) => **current,
+            Err(_) => self.settings.speed,
+        };
+        if let Ok(duration) = Duration::try_from_secs_f64(seconds / speed) {
+            std::thread::sleep(duration);
+        }
+    }
+}
+
+impl<S: Stage> Stage for PacedStage<S> {
+    fn perform(&mut self, event: ScriptEvent) {
+        let seconds = match event {
+            ScriptEvent::Fragment(index, _) if index > 0 => self.settings.scene_pause,
+            ScriptEvent::Enter(_) | ScriptEvent::Exit(_) => self.settings.direction_pause,
+            ScriptEvent::Speaker(_) => self.settings.speaker_pause,
+            ScriptEvent::Line(_, ref text) => self.settings.speaking_time(text),
+            ScriptEvent::Pause(seconds) => seconds,
+            _ => 0.0,
+        };
+        if !self.settings.estimate_only {
+            self.inner.perform(event);
+        }
+        self.hold(seconds);
+    }
+
+    fn step(&mut self) -> Step {
+        self.inner.step()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_must_be_finite_and_bounded() {
+        assert!(is_valid_pause(0.0));
+        assert!(is_valid_pause(MAX_PAUSE));
+        for seconds in [-1.0, f64::INFINITY, f64::NAN, 1e300, MAX_PAUSE + 1.0] {
+            assert!(!is_valid_pause(seconds), "{} accepted", seconds);
+        }
+        assert!(!is_valid_speed(1e-300));
+        assert!(!is_valid_speed(f64::INFINITY));
+    }
+
+    #[test]
+    fn estimate_never_panics_on_a_huge_total() {
+        let settings = PaceSettings { estimate_only: true, ..PaceSettings::default() };
+        let mut stage = PacedStage::new(Vec::<ScriptEvent>::new(), settings);
+        stage.perform(ScriptEvent::Pause(f64::MAX));
+        stage.perform(ScriptEvent::Pause(f64::MAX));
+        assert_eq!(stage.running_time(), Duration::MAX);
+    }
+}
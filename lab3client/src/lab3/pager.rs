@@ -0,0 +1,512 @@
+/// pager.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A full-screen terminal browser over a prepared play. The play is recited once and laid out like
+/// the terminal renderer, with line numbers in a gutter, on the terminal's alternate screen. The reader can
+/// scroll, jump to a scene title or a line number, search with a regular expression, and filter the text
+/// to one character's lines, keeping the cue (the last line of the speech before) ahead of each of their
+/// speeches. The keys follow less; h shows them.
+use std::io::Write;
+use super::play::Play;
+use super::stage::ScriptEvent;
+use super::regex::{Regex, has_uppercase};
+use super::terminal::{RawTerminal, Key, terminal_size, wrap, char_width, display_width, character_color,
+                      BOLD, DIM, REVERSE, RESET};
+use super::declarations::{BAD_COMMAND_LINE_ERROR, FAILED_TO_WRITE_OUTPUT};
+
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h\x1b[?25l";   // and hide the cursor
+const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const HOME_CURSOR: &str = "\x1b[H";
+const CLEAR_TO_END_OF_LINE: &str = "\x1b[K";
+const DEFAULT_SIZE: (usize, usize) = (24, 80);    // (rows, columns) if the terminal will not say
+const GUTTER_WIDTH: usize = 6;                      // a line number right aligned in 4 columns, then 2 spaces
+const HELP: &str = "q quit  j/k line  space/b page  d/u half  g/G ends  [/] scene  :jump  /? search  n/N again  \
+                    c character  h help";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Title,
+    Direction,
+    Speaker,
+    Line,
+    Blank,
+}
+
+/// One line of the laid-out recitation
+#[derive(Clone, Debug)]
+struct Entry {
+    kind: Kind,
+    text: String,
+    fragment: usize,
+    character: String,          // speaker of a heading or line, or the character entering or exiting
+    line_number: Option<usize>,
+}
+
+impl Entry {
+    fn blank(fragment: usize) -> Entry {
+        Entry { kind: Kind::Blank, text: String::new(), fragment, character: String::new(), line_number: None }
+    }
+}
+
+/// A screen row: all or part of an entry, wrapped to the screen width
+struct Row {
+    entry: usize,
+    text: String,
+    first: bool,    // the entry's first row, which shows its line number
+}
+
+/// Lays the recitation out in the plain text format, one entry per printed line
+fn layout(events: &[ScriptEvent]) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut fragment: usize = 0;
+    let mut speaker = String::new();
+    for event in events {
+        let entry = |kind: Kind, text: String, character: &str, line_number: Option<usize>| -> Entry {
+            Entry { kind, text, fragment, character: character.to_string(), line_number }
+        };
+        match event {
+            ScriptEvent::Fragment(index, title) => {
+                fragment = *index;
+                if !title.trim().is_empty() {
+                    if *index > 0 {
+                        entries.push(Entry::blank(fragment));
+                    }
+                    entries.push(Entry { kind: Kind::Title, text: title.clone(), fragment, character: String::new(),
+                                         line_number: None });
+                    entries.push(Entry::blank(fragment));
+                }
+            }
+            ScriptEvent::Enter(name) => entries.push(entry(Kind::Direction, format!("[Enter {}.]", name), name, None)),
+            ScriptEvent::Exit(name) => entries.push(entry(Kind::Direction, format!("[Exit {}.]", name), name, None)),
+            ScriptEvent::Speaker(name) => {
+                speaker = name.clone();
+                entries.push(Entry::blank(fragment));
+                entries.push(entry(Kind::Speaker, format!("{}.", name), name, None));
+            }
+            ScriptEvent::Line(line_number, text) => {
+                entries.push(entry(Kind::Line, text.clone(), &speaker, Some(*line_number)));
+            }
+            ScriptEvent::EndOfDialogue => entries.push(Entry::blank(fragment)),
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) => {}
+        }
+    }
+    entries
+}
+
+/// The entries that concern one character: scene titles, their entrances and exits and speeches, and the
+/// heading and last line of the speech before each of theirs
+fn filter_entries(entries: &[Entry], character: &str) -> Vec<Entry> {
+    let mut view: Vec<Entry> = Vec::new();
+    let mut cue: Option<(usize, usize)> = None;     // (heading, last line) of the latest speech by someone else
+    for (i, entry) in entries.iter().enumerate() {
+        let own = entry.character == character;
+        match entry.kind {
+            Kind::Title => {
+                if !view.is_empty() {
+                    view.push(Entry::blank(entry.fragment));
+                }
+                view.push(entry.clone());
+                cue = None;
+            }
+            Kind::Direction if own => view.push(entry.clone()),
+            Kind::Speaker if own => {
+                if let Some((heading, line)) = cue.take() {
+                    if line != heading && entries[heading].fragment == entry.fragment {
+                        view.push(Entry::blank(entry.fragment));
+                        view.push(entries[heading].clone());
+                        view.push(entries[line].clone());
+                    }
+                }
+                view.push(Entry::blank(entry.fragment));
+                view.push(entry.clone());
+            }
+            Kind::Speaker => cue = Some((i, i)),
+            Kind::Line if own => view.push(entry.clone()),
+            Kind::Line => {
+                if let Some((_, ref mut line)) = cue {
+                    *line = i;
+                }
+            }
+            _ => {}
+        }
+    }
+    view
+}
+
+/// Cuts text to at most width columns
+fn truncate(text: &str, width: usize) -> String {
+    let mut columns = 0;
+    text.chars().take_while(|c| {
+        columns += char_width(*c);
+        columns <= width
+    }).collect()
+}
+
+/// Pads text with spaces to width columns, cutting it if it is wider
+fn pad(text: &str, width: usize) -> String {
+    let text = truncate(text, width);
+    let padding = width - display_width(&text);
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+struct Pager {
+    entries: Vec<Entry>,        // the whole recitation
+    view: Vec<Entry>,           // what is shown: the whole recitation or one character's part of it
+    rows: Vec<Row>,
+    titles: Vec<String>,        // by fragment index
+    characters: Vec<String>,
+    filter: Option<String>,
+    search: Option<Regex>,
+    width: usize,
+    height: usize,              // rows of text, above the status line
+    top: usize,                 // first row shown
+    message: String,
+}
+
+impl Pager {
+    fn new(events: &[ScriptEvent], characters: Vec<String>) -> Pager {
+        let entries = layout(events);
+        let titles = events.iter().filter_map(|event| match event {
+            ScriptEvent::Fragment(_, title) => Some(title.clone()),
+            _ => None,
+        }).collect();
+        Pager {
+            view: entries.clone(),
+            entries,
+            rows: Vec::new(),
+            titles,
+            characters,
+            filter: None,
+            search: None,
+            width: 0,
+            height: 0,
+            top: 0,
+            message: "h for help".to_string(),
+        }
+    }
+
+    /// Wraps the view to the screen width
+    fn lay_out_rows(&mut self) {
+        let text_width = self.width.saturating_sub(GUTTER_WIDTH).max(1);
+        self.rows.clear();
+        for (i, entry) in self.view.iter().enumerate() {
+            let lines = if entry.kind == Kind::Blank { vec![String::new()] } else { wrap(&entry.text, text_width) };
+            for (n, text) in lines.into_iter().enumerate() {
+                self.rows.push(Row { entry: i, text, first: n == 0 });
+            }
+        }
+    }
+
+    fn current_entry(&self) -> usize {
+        self.rows.get(self.top).map(|row| row.entry).unwrap_or(0)
+    }
+
+    fn current_fragment(&self) -> usize {
+        self.view.get(self.current_entry()).map(|entry| entry.fragment).unwrap_or(0)
+    }
+
+    fn max_top(&self) -> usize {
+        self.rows.len().saturating_sub(self.height)
+    }
+
+    /// Shows the given entry at the top of the screen, or as near as the end of the text allows
+    fn go_to_entry(&mut self, entry: usize) {
+        let row = self.rows.iter().position(|row| row.entry >= entry).unwrap_or(self.rows.len());
+        self.top = row.min(self.max_top());
+    }
+
+    /// Follows the terminal's size, keeping the same entry at the top
+    fn resize(&mut self) {
+        let (rows, columns) = terminal_size().unwrap_or(DEFAULT_SIZE);
+        if columns != self.width || rows.saturating_sub(1) != self.height {
+            let entry = self.current_entry();
+            self.width = columns;
+            self.height = rows.saturating_sub(1).max(1);
+            self.lay_out_rows();
+            self.go_to_entry(entry);
+        }
+    }
+
+    fn scroll(&mut self, rows: isize) {
+        self.top = self.top.saturating_add_signed(rows).min(self.max_top());
+    }
+
+    /// Switches between the whole recitation and one character's part, staying in the same scene
+    fn set_filter(&mut self, filter: Option<String>) {
+        let fragment = self.current_fragment();
+        self.view = match filter {
+            Some(ref character) => filter_entries(&self.entries, character),
+            None => self.entries.clone(),
+        };
+        self.filter = filter;
+        self.lay_out_rows();
+        let entry = self.view.iter().position(|entry| entry.fragment >= fragment).unwrap_or(0);
+        self.go_to_entry(entry);
+    }
+
+    /// Finds a character by name, ignoring case, or by a prefix that names only one
+    fn find_character(&self, name: &str) -> Option<String> {
+        let name = name.to_lowercase();
+        if let Some(exact) = self.characters.iter().find(|c| c.to_lowercase() == name) {
+            return Some(exact.clone());
+        }
+        let matches: Vec<&String> = self.characters.iter().filter(|c| c.to_lowercase().starts_with(&name)).collect();
+        match matches.as_slice() {
+            [only] => Some((*only).clone()),
+            _ => None,
+        }
+    }
+
+    fn next_scene(&mut self) {
+        let fragment = self.current_fragment();
+        match self.view.iter().position(|entry| entry.fragment > fragment) {
+            Some(entry) => self.go_to_entry(entry),
+            None => self.message = "Last scene".to_string(),
+        }
+    }
+
+    /// Goes to the start of the scene shown, or the scene before if already at its start
+    fn previous_scene(&mut self) {
+        let fragment = self.current_fragment();
+        let start = self.view.iter().position(|entry| entry.fragment == fragment).unwrap_or(0);
+        let target = if start < self.current_entry() {
+            Some(fragment)
+        } else {
+            self.view[..start].last().map(|entry| entry.fragment)
+        };
+        match target {
+            Some(target) => {
+                let entry = self.view.iter().position(|entry| entry.fragment == target).unwrap_or(0);
+                self.go_to_entry(entry);
+            }
+            None => self.message = "First scene".to_string(),
+        }
+    }
+
+    /// Jumps to a line number, in the scene shown if it has one, or to the next scene whose title holds text
+    fn jump(&mut self, target: &str) {
+        let target = target.trim();
+        if let Ok(line_number) = target.parse::<usize>() {
+            let fragment = self.current_fragment();
+            let is_line = |entry: &Entry| entry.line_number == Some(line_number);
+            let found = self.view.iter().position(|entry| is_line(entry) && entry.fragment == fragment)
+                .or_else(|| self.view.iter().position(is_line));
+            match found {
+                Some(entry) => self.go_to_entry(entry),
+                None => self.message = format!("No line {}", line_number),
+            }
+            return;
+        }
+
+        let text = target.to_lowercase();
+        let current = self.current_entry();
+        let is_title = |entry: &Entry| entry.kind == Kind::Title && entry.text.to_lowercase().contains(&text);
+        let found = self.view.iter().skip(current + 1).position(is_title).map(|offset| current + 1 + offset)
+            .or_else(|| self.view.iter().position(is_title));
+        match found {
+            Some(entry) => self.go_to_entry(entry),
+            None => self.message = format!("No scene titled '{}'", target),
+        }
+    }
+
+    /// Moves to the next entry after (or before) the top one that matches the search
+    fn find_next(&mut self, forward: bool) {
+        let regex = match self.search {
+            Some(ref regex) => regex,
+            None => {
+                self.message = "No previous search".to_string();
+                return;
+            }
+        };
+        let current = self.current_entry();
+        let order: Vec<usize> = if forward {
+            (current + 1..self.view.len()).collect()
+        } else {
+            (0..current).rev().collect()
+        };
+        let mut found: Result<Option<usize>, String> = Ok(None);
+        for entry in order {
+            if self.view[entry].kind == Kind::Blank {
+                continue;
+            }
+            match regex.is_match(&self.view[entry].text) {
+                Ok(false) => {}
+                Ok(true) => found = Ok(Some(entry)),
+                Err(e) => found = Err(e),
+            }
+            if !matches!(found, Ok(None)) {
+                break;
+            }
+        }
+        match found {
+            Ok(Some(entry)) => self.go_to_entry(entry),
+            Ok(None) => self.message = "Pattern not found".to_string(),
+            Err(e) => self.message = format!("Bad pattern: {}", e),
+        }
+    }
+
+    fn style(entry: &Entry) -> String {
+        match entry.kind {
+            Kind::Title => BOLD.to_string(),
+            Kind::Direction => DIM.to_string(),
+            Kind::Speaker => format!("{}{}", BOLD, character_color(&entry.character)),
+            Kind::Line => character_color(&entry.character).to_string(),
+            Kind::Blank => String::new(),
+        }
+    }
+
+    /// A row's text in its entry's style, with any search match in reverse video
+    fn render_row(&self, row: &Row) -> String {
+        let entry = &self.view[row.entry];
+        let style = Self::style(entry);
+        let gutter = match entry.line_number {
+            Some(line_number) if row.first => format!("{}{:>4}{}  ", DIM, line_number, RESET),
+            _ => " ".repeat(GUTTER_WIDTH),
+        };
+        let text = truncate(&row.text, self.width.saturating_sub(GUTTER_WIDTH));
+        let found = self.search.as_ref().and_then(|regex| regex.find(&text).ok().flatten()).filter(|(start, end)| start < end);
+        match found {
+            Some((start, end)) => format!("{}{}{}{}{}{}{}{}{}{}", gutter, style, &text[..start], RESET, REVERSE,
+                                          &text[start..end], RESET, style, &text[end..], RESET),
+            None => format!("{}{}{}{}", gutter, style, text, RESET),
+        }
+    }
+
+    fn status(&self) -> String {
+        if !self.message.is_empty() {
+            return self.message.clone();
+        }
+        let fragment = self.current_fragment();
+        let title = self.titles.get(fragment).map(|t| t.trim()).filter(|t| !t.is_empty())
+            .map(|t| t.to_string()).unwrap_or_else(|| format!("Scene {}", fragment + 1));
+        let filter = self.filter.as_ref().map(|c| format!(" [{}]", c)).unwrap_or_default();
+        let bottom = (self.top + self.height).min(self.rows.len());
+        let position = if self.rows.len() <= self.height {
+            "all".to_string()
+        } else {
+            format!("{}%", bottom * 100 / self.rows.len())
+        };
+        let right = format!(" {}/{} {}", fragment + 1, self.titles.len(), position);
+        let left_width = self.width.saturating_sub(display_width(&right));
+        format!("{}{}", pad(&format!("{}{}", title, filter), left_width), right)
+    }
+
+    /// Redraws the screen with the given status line text
+    fn draw(&self, out: &mut dyn Write, status: &str) -> std::io::Result<()> {
+        let mut frame = String::from(HOME_CURSOR);
+        for r in 0..self.height {
+            if let Some(row) = self.rows.get(self.top + r) {
+                frame.push_str(&self.render_row(row));
+            }
+            frame.push_str(CLEAR_TO_END_OF_LINE);
+            frame.push_str("\r\n");
+        }
+        frame.push_str(&format!("{}{}{}", REVERSE, pad(status, self.width), RESET));
+        out.write_all(frame.as_bytes())?;
+        out.flush()
+    }
+
+    /// Reads a line of input on the status line; None if the reader cancels with escape or ctrl-c
+    fn prompt(&self, terminal: &mut RawTerminal, label: &str) -> std::io::Result<Option<String>> {
+        let mut input = String::new();
+        loop {
+            self.draw(terminal, &format!("{}{}_", label, input))?;
+            match terminal.read_key() {
+                Some(Key::Enter) => return Ok(Some(input)),
+                Some(Key::Escape) | Some(Key::Interrupt) | None => return Ok(None),
+                Some(Key::Backspace) => {
+                    input.pop();
+                }
+                Some(Key::Char(c)) if !c.is_control() => input.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    fn run(&mut self, terminal: &mut RawTerminal) -> std::io::Result<()> {
+        write!(terminal, "{}", ENTER_ALTERNATE_SCREEN)?;
+        loop {
+            self.resize();
+            self.draw(terminal, &self.status())?;
+            let key = match terminal.read_key() {
+                Some(key) => key,
+                None => return Ok(()),
+            };
+            self.message.clear();
+            let page = self.height as isize;
+            match key {
+                Key::Char('q') | Key::Interrupt => return Ok(()),
+                Key::Char('j') | Key::Down | Key::Enter => self.scroll(1),
+                Key::Char('k') | Key::Up => self.scroll(-1),
+                Key::Char(' ') | Key::Char('f') | Key::PageDown => self.scroll(page),
+                Key::Char('b') | Key::PageUp => self.scroll(-page),
+                Key::Char('d') => self.scroll(page / 2),
+                Key::Char('u') => self.scroll(-page / 2),
+                Key::Char('g') | Key::Home => self.top = 0,
+                Key::Char('G') | Key::End => self.top = self.max_top(),
+                Key::Char(']') => self.next_scene(),
+                Key::Char('[') => self.previous_scene(),
+                Key::Char('n') => self.find_next(true),
+                Key::Char('N') => self.find_next(false),
+                Key::Char('h') => self.message = HELP.to_string(),
+                Key::Char(':') => {
+                    if let Some(target) = self.prompt(terminal, "line or scene: ")? {
+                        self.jump(&target);
+                    }
+                }
+                Key::Char(c @ ('/' | '?')) => {
+                    if let Some(pattern) = self.prompt(terminal, &c.to_string())? {
+                        if !pattern.is_empty() {
+                            match Regex::new(&pattern, !has_uppercase(&pattern)) {
+                                Ok(regex) => self.search = Some(regex),
+                                Err(e) => {
+                                    self.message = format!("Bad pattern: {}", e);
+                                    continue;
+                                }
+                            }
+                        }
+                        self.find_next(c == '/');
+                    }
+                }
+                Key::Char('c') => {
+                    if let Some(name) = self.prompt(terminal, "character (empty for all): ")? {
+                        if name.trim().is_empty() {
+                            self.set_filter(None);
+                        } else {
+                            match self.find_character(name.trim()) {
+                                Some(character) => self.set_filter(Some(character)),
+                                None => self.message = format!("No character named '{}'", name.trim()),
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Browses the play on the terminal until the reader quits, starting filtered to a character if one is given
+pub fn browse(play: &mut Play, character: Option<&String>) -> Result<(), u8> {
+    let mut events: Vec<ScriptEvent> = Vec::new();
+    play.recite(&mut events);
+    let mut pager = Pager::new(&events, play.characters());
+    if let Some(character) = character {
+        if !pager.characters.contains(character) {
+            writeln!(std::io::stderr().lock(), "Error: '{}' is not a character in the play", character)
+                .expect("Failed to write to stderr");
+            return Err(BAD_COMMAND_LINE_ERROR);
+        }
+        pager.resize();
+        pager.set_filter(Some(character.clone()));
+    }
+
+    let mut terminal = RawTerminal::open()?;
+    let result = pager.run(&mut terminal);
+    let restored = write!(terminal, "{}", LEAVE_ALTERNATE_SCREEN).and_then(|_| terminal.flush());
+    if let Err(e) = result.and(restored) {
+        writeln!(std::io::stderr().lock(), "Error: Failed to write to the terminal: {}", e)
+            .expect("Failed to write to stderr");
+        return Err(FAILED_TO_WRITE_OUTPUT);
+    }
+    Ok(())
+}
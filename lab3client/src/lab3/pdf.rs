@@ -0,0 +1,440 @@
+/// pdf.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Typesets a recitation as a PDF in the classic play-script style, without external tools. Every
+/// fragment starts a new page with its title centered in bold; speeches open with the speaker's name in small
+/// caps and hang their following lines from an indent; enter and exit directives are set in italics; every
+/// fifth line (or the lines the numbering asks for) is numbered or cited in the right margin; and each page is
+/// headed by the play title and current scene.
+/// Text uses the standard Times fonts every PDF reader has, so nothing is embedded.
+use std::io::Write;
+use super::stage::ScriptEvent;
+use super::numbering::{Numbering, LineLabels};
+
+const PAGE_WIDTH: f64 = 612.0;          // US Letter, in points
+const PAGE_HEIGHT: f64 = 792.0;
+const MARGIN: f64 = 72.0;
+const HEADER_BASELINE: f64 = PAGE_HEIGHT - 48.0;
+const FOOTER_BASELINE: f64 = 40.0;
+const LINE_NUMBER_GUTTER: f64 = 36.0;   // space kept clear of text for right-margin line numbers
+
+const BODY_SIZE: f64 = 11.0;
+const BODY_LEADING: f64 = 14.0;
+const TITLE_SIZE: f64 = 13.0;
+const TITLE_LEADING: f64 = 17.0;
+const HEADER_SIZE: f64 = 9.0;
+const LINE_NUMBER_SIZE: f64 = 8.0;
+const SMALL_CAPS_SCALE: f64 = 0.8;
+const SPEECH_INDENT: f64 = 36.0;        // hanging indent for the lines after a speech's first
+const WRAP_INDENT: f64 = 18.0;          // further indent for the rest of a line too long to fit
+const DIRECTION_INDENT: f64 = 108.0;
+const LINE_NUMBER_INTERVAL: usize = 5;
+const ELLIPSIS: &str = "...";
+
+/// Advance widths in thousandths of the font size for codes 32 to 126, from the Adobe font metrics
+const TIMES_ROMAN_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+const TIMES_BOLD_WIDTHS: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+const TIMES_ITALIC_WIDTHS: [u16; 95] = [
+    250, 333, 420, 500, 500, 833, 778, 214, 333, 333, 500, 675, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 675, 675, 675, 500,
+    920, 611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833, 667, 722,
+    611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556, 389, 278, 389, 422, 500,
+    333, 500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722, 500, 500,
+    500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389, 400, 275, 400, 541,
+];
+const DEFAULT_WIDTH: u16 = 500;         // for characters outside the tables
+
+#[derive(Clone, Copy, PartialEq)]
+enum Font {
+    Roman,
+    Bold,
+    Italic,
+}
+
+impl Font {
+    const ALL: [Font; 3] = [Font::Roman, Font::Bold, Font::Italic];
+
+    fn resource_name(self) -> &'static str {
+        match self {
+            Font::Roman => "F1",
+            Font::Bold => "F2",
+            Font::Italic => "F3",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Roman => "Times-Roman",
+            Font::Bold => "Times-Bold",
+            Font::Italic => "Times-Italic",
+        }
+    }
+
+    fn widths(self) -> &'static [u16; 95] {
+        match self {
+            Font::Roman => &TIMES_ROMAN_WIDTHS,
+            Font::Bold => &TIMES_BOLD_WIDTHS,
+            Font::Italic => &TIMES_ITALIC_WIDTHS,
+        }
+    }
+
+    fn text_width(self, size: f64, text: &str) -> f64 {
+        let thousandths: u32 = text.chars().map(|c| match c {
+            ' '..='~' => self.widths()[c as usize - 32] as u32,
+            _ => DEFAULT_WIDTH as u32,
+        }).sum();
+        thousandths as f64 * size / 1000.0
+    }
+}
+
+/// A piece of text set in one font and size
+struct Run {
+    font: Font,
+    size: f64,
+    text: String,
+}
+
+fn runs_width(runs: &[Run]) -> f64 {
+    runs.iter().map(|run| run.font.text_width(run.size, &run.text)).sum()
+}
+
+/// A name in small caps: capitals at full size, lowercase letters as smaller capitals
+fn small_caps(name: &str, size: f64) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for c in name.chars() {
+        let (text, size) = if c.is_lowercase() {
+            (c.to_uppercase().collect::<String>(), size * SMALL_CAPS_SCALE)
+        } else {
+            (c.to_string(), size)
+        };
+        match runs.last_mut() {
+            Some(run) if run.size == size => run.text.push_str(&text),
+            _ => runs.push(Run { font: Font::Roman, size, text }),
+        }
+    }
+    runs
+}
+
+/// Breaks text into lines at spaces, the first at most first_width wide and the rest at most rest_width.
+/// A word too long for a line is left on a line of its own.
+fn wrap(text: &str, font: Font, size: f64, first_width: f64, rest_width: f64) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let width = if lines.is_empty() { first_width } else { rest_width };
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+        if current.is_empty() || font.text_width(size, &candidate) <= width {
+            current = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+/// Shortens text to fit the width, ending it with an ellipsis
+fn fit(text: &str, font: Font, size: f64, width: f64) -> String {
+    if font.text_width(size, text) <= width {
+        return text.to_string();
+    }
+    let mut fitted: String = text.to_string();
+    while !fitted.is_empty() && font.text_width(size, &format!("{}{}", fitted.trim_end(), ELLIPSIS)) > width {
+        fitted.pop();
+    }
+    format!("{}{}", fitted.trim_end(), ELLIPSIS)
+}
+
+/// The byte for a character in WinAnsiEncoding, which the standard fonts use
+fn win_ansi(c: char) -> u8 {
+    match c {
+        '\u{2026}' => 0x85,
+        '\u{2018}' => 0x91,
+        '\u{2019}' => 0x92,
+        '\u{201C}' => 0x93,
+        '\u{201D}' => 0x94,
+        '\u{2013}' => 0x96,
+        '\u{2014}' => 0x97,
+        ' '..='~' | '\u{A0}'..='\u{FF}' => c as u8,
+        _ => b'?',
+    }
+}
+
+/// A PDF string literal, keeping the file ASCII by writing other bytes as octal escapes
+fn pdf_string(text: &str) -> String {
+    let mut literal = String::from("(");
+    for byte in text.chars().map(win_ansi) {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+/// Lays text out top to bottom, starting new pages as they fill
+struct Layout {
+    pages: Vec<String>,     // finished content streams
+    content: String,        // content stream of the page being set
+    y: f64,                 // baseline of the next line
+    page_has_body: bool,
+    play_title: String,
+    scene_title: String,
+}
+
+impl Layout {
+    fn new(play_title: String) -> Layout {
+        Layout {
+            pages: Vec::new(),
+            content: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+            page_has_body: false,
+            play_title,
+            scene_title: String::new(),
+        }
+    }
+
+    fn draw(&mut self, x: f64, y: f64, runs: &[Run]) {
+        self.content.push_str(&format!("BT {:.2} {:.2} Td", x, y));
+        for run in runs {
+            self.content.push_str(&format!(" /{} {:.1} Tf {} Tj", run.font.resource_name(), run.size,
+                                           pdf_string(&run.text)));
+        }
+        self.content.push_str(" ET\n");
+    }
+
+    /// Starts a page with its header (play title at the left, scene at the right, above a rule) and number
+    fn start_page(&mut self) {
+        let page_number = self.pages.len() + 1;
+        let width = PAGE_WIDTH - 2.0 * MARGIN;
+        if self.scene_title == self.play_title {
+            // the opening scene usually names the play, so the title alone heads its pages
+            let play = fit(&self.play_title, Font::Italic, HEADER_SIZE, width);
+            self.draw(MARGIN, HEADER_BASELINE, &[Run { font: Font::Italic, size: HEADER_SIZE, text: play }]);
+        } else {
+            let half = width / 2.0 - HEADER_SIZE;
+            let play = fit(&self.play_title, Font::Italic, HEADER_SIZE, half);
+            let scene = fit(&self.scene_title, Font::Italic, HEADER_SIZE, half);
+            let scene_x = PAGE_WIDTH - MARGIN - Font::Italic.text_width(HEADER_SIZE, &scene);
+            self.draw(MARGIN, HEADER_BASELINE, &[Run { font: Font::Italic, size: HEADER_SIZE, text: play }]);
+            self.draw(scene_x, HEADER_BASELINE, &[Run { font: Font::Italic, size: HEADER_SIZE, text: scene }]);
+        }
+        self.content.push_str(&format!("0.5 w {:.2} {:.2} m {:.2} {:.2} l S\n",
+                                       MARGIN, HEADER_BASELINE - 4.0, PAGE_WIDTH - MARGIN, HEADER_BASELINE - 4.0));
+        let number = page_number.to_string();
+        let number_x = (PAGE_WIDTH - Font::Roman.text_width(HEADER_SIZE, &number)) / 2.0;
+        self.draw(number_x, FOOTER_BASELINE, &[Run { font: Font::Roman, size: HEADER_SIZE, text: number }]);
+        self.y = PAGE_HEIGHT - MARGIN;
+        self.page_has_body = false;
+    }
+
+    fn end_page(&mut self) {
+        if !self.content.is_empty() {
+            self.pages.push(std::mem::take(&mut self.content));
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.end_page();
+        self.start_page();
+    }
+
+    /// Moves to a new page unless the current one is still empty
+    fn page_break(&mut self) {
+        if self.content.is_empty() || self.page_has_body {
+            self.new_page();
+        }
+    }
+
+    /// Sets one line of the body, first moving to a new page if it would not fit
+    fn line(&mut self, x: f64, leading: f64, runs: &[Run]) {
+        if self.y - leading < MARGIN {
+            self.new_page();
+        }
+        self.y -= leading;
+        let y = self.y;
+        self.draw(x, y, runs);
+        self.page_has_body = true;
+    }
+
+    fn space(&mut self, height: f64) {
+        if self.page_has_body {
+            self.y -= height;
+        }
+    }
+
+    fn line_number(&mut self, label: String) {
+        let text = label;
+        let x = PAGE_WIDTH - MARGIN - Font::Roman.text_width(LINE_NUMBER_SIZE, &text);
+        let y = self.y;
+        self.draw(x, y, &[Run { font: Font::Roman, size: LINE_NUMBER_SIZE, text }]);
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if self.pages.is_empty() && self.content.is_empty() {
+            self.start_page();     // even an empty play gets a page
+        }
+        self.end_page();
+        self.pages
+    }
+}
+
+/// Lays out the recitation, returning one content stream per page
+fn typeset(events: &[ScriptEvent], play_title: String, numbering: Numbering) -> Vec<String> {
+    let text_right = PAGE_WIDTH - MARGIN - LINE_NUMBER_GUTTER;
+    let mut layout = Layout::new(play_title);
+    let mut labels = LineLabels::new(numbering);
+    let mut speaker: Option<String> = None;
+    let mut after_directions = false;
+
+    for event in events {
+        labels.observe(event);
+        if after_directions && !matches!(event, ScriptEvent::Enter(_) | ScriptEvent::Exit(_)) {
+            layout.space(BODY_LEADING / 2.0);
+            after_directions = false;
+        }
+        match event {
+            ScriptEvent::Fragment(_, title) => {
+                if !title.trim().is_empty() {
+                    layout.scene_title = title.clone();
+                }
+                layout.page_break();
+                if !title.trim().is_empty() {
+                    for title_line in wrap(title, Font::Bold, TITLE_SIZE, text_right - MARGIN, text_right - MARGIN) {
+                        let x = (PAGE_WIDTH - Font::Bold.text_width(TITLE_SIZE, &title_line)) / 2.0;
+                        layout.line(x, TITLE_LEADING, &[Run { font: Font::Bold, size: TITLE_SIZE, text: title_line }]);
+                    }
+                    layout.space(TITLE_LEADING);
+                }
+            }
+            ScriptEvent::Enter(name) | ScriptEvent::Exit(name) => {
+                let verb = if matches!(event, ScriptEvent::Enter(_)) { "Enter" } else { "Exit" };
+                let x = MARGIN + DIRECTION_INDENT;
+                let text = format!("{} {}.", verb, name);
+                for direction_line in wrap(&text, Font::Italic, BODY_SIZE, text_right - x, text_right - x) {
+                    layout.line(x, BODY_LEADING, &[Run { font: Font::Italic, size: BODY_SIZE, text: direction_line }]);
+                }
+                after_directions = true;
+            }
+            ScriptEvent::Speaker(name) => {
+                layout.space(BODY_LEADING / 2.0);
+                speaker = Some(name.clone());
+            }
+            ScriptEvent::Line(number, text) => {
+                // The speech's first line follows the speaker's name; the rest hang from the indent
+                let mut first_runs = match speaker.take() {
+                    Some(name) => {
+                        let mut runs = small_caps(&name, BODY_SIZE);
+                        runs.push(Run { font: Font::Roman, size: BODY_SIZE, text: ". ".to_string() });
+                        runs
+                    }
+                    None => Vec::new(),
+                };
+                let first_x = if first_runs.is_empty() { MARGIN + SPEECH_INDENT } else { MARGIN };
+                let first_width = text_right - first_x - runs_width(&first_runs);
+                let rest_x = MARGIN + SPEECH_INDENT + WRAP_INDENT;
+                let lines = wrap(text, Font::Roman, BODY_SIZE, first_width, text_right - rest_x);
+                for (i, text_line) in lines.into_iter().enumerate() {
+                    if i == 0 {
+                        match first_runs.last_mut() {
+                            Some(run) if run.size == BODY_SIZE => run.text.push_str(&text_line),
+                            _ => first_runs.push(Run { font: Font::Roman, size: BODY_SIZE, text: text_line }),
+                        }
+                        layout.line(first_x, BODY_LEADING, &first_runs);
+                        if let Some(label) = labels.label(*number) {
+                            layout.line_number(label);
+                        }
+                    } else {
+                        layout.line(rest_x, BODY_LEADING, &[Run { font: Font::Roman, size: BODY_SIZE, text: text_line }]);
+                    }
+                }
+            }
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) | ScriptEvent::EndOfDialogue => {}
+        }
+    }
+    layout.finish()
+}
+
+/// Writes the PDF objects, then the cross-reference table that locates them
+fn write_document(pages: &[String], title: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    const PAGES_ID: usize = 2;
+    const FIRST_FONT_ID: usize = 3;
+    const INFO_ID: usize = FIRST_FONT_ID + Font::ALL.len();
+    const FIRST_PAGE_ID: usize = INFO_ID + 1;   // each page is followed by its content stream
+
+    let mut objects: Vec<String> = Vec::new();
+    objects.push(format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES_ID));
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", FIRST_PAGE_ID + 2 * i)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()));
+    for font in Font::ALL {
+        objects.push(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                             font.base_font()));
+    }
+    objects.push(format!("<< /Title {} /Producer (lab3client) >>", pdf_string(title)));
+    let fonts: Vec<String> = Font::ALL.iter().enumerate()
+        .map(|(i, font)| format!("/{} {} 0 R", font.resource_name(), FIRST_FONT_ID + i))
+        .collect();
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {} >> >> \
+                              /Contents {} 0 R >>",
+                             PAGES_ID, PAGE_WIDTH, PAGE_HEIGHT, fonts.join(" "), FIRST_PAGE_ID + 2 * i + 1));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    let mut offsets: Vec<usize> = Vec::new();
+    let header = "%PDF-1.4\n";
+    out.write_all(header.as_bytes())?;
+    let mut offset = header.len();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(offset);
+        let text = format!("{} 0 obj\n{}\nendobj\n", i + 1, object);
+        out.write_all(text.as_bytes())?;
+        offset += text.len();
+    }
+
+    writeln!(out, "xref")?;
+    writeln!(out, "0 {}", objects.len() + 1)?;
+    write!(out, "0000000000 65535 f\r\n")?;
+    for object_offset in offsets {
+        write!(out, "{:010} 00000 n\r\n", object_offset)?;
+    }
+    writeln!(out, "trailer")?;
+    writeln!(out, "<< /Size {} /Root 1 0 R /Info {} 0 R >>", objects.len() + 1, INFO_ID)?;
+    writeln!(out, "startxref")?;
+    writeln!(out, "{}", offset)?;
+    writeln!(out, "%%EOF")
+}
+
+/// Writes the whole recitation as a PDF titled after the first fragment, numbering every fifth line unless the
+/// numbering asks for other labels
+pub fn write_pdf(events: &[ScriptEvent], numbering: Numbering, out: &mut dyn Write) -> std::io::Result<()> {
+    let play_title = events.iter().find_map(|event| match event {
+        ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => Some(title.clone()),
+        _ => None,
+    }).unwrap_or_default();
+    let numbering = if numbering.is_on() {
+        numbering
+    } else {
+        Numbering { every: LINE_NUMBER_INTERVAL, citations: false }
+    };
+    let pages = typeset(events, play_title.clone(), numbering);
+    write_document(&pages, &play_title, out)
+}
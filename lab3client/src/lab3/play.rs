@@ -3,13 +3,17 @@
 /// Summary: This module implements the core Play structure that orchestrates a performance by managing scene fragments.
 use std::sync::atomic::Ordering;
 use std::io::Write;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use super::scene_fragment::SceneFragment;
+use super::stage::{Stage, ScriptEvent};
 use super::declarations::{WHINGE_MODE, SCRIPT_PARSING_ERROR};
 use super::script_gen::grab_trimmed_file_lines;
+use super::model::{is_model_file, load_play_json};
+use super::bundle::{is_bundle_file, script_member, sibling_path};
 
 pub type ScriptConfig = Vec<(bool, String)>;
 pub type Fragments = Vec<Arc<Mutex<SceneFragment>>>;
+pub type CastChange = (Vec<String>, Vec<String>); // (entering, exiting) around one fragment
 
 const CONFIG_FILENAME_INDEX: usize = 0;
 const CONFIG_SCRIPT_LENGTH: usize = 1;
@@ -27,6 +31,13 @@ impl Play {
         }
     }
 
+    /// Creates a play from already prepared scene fragments
+    pub fn from_fragments(fragments: Vec<SceneFragment>) -> Play {
+        Play {
+            fragments: fragments.into_iter().map(|f| Arc::new(Mutex::new(f))).collect(),
+        }
+    }
+
     /// Converts the ScriptConfig into SceneFragment objects:
     /// - Scene titles are stored temporarily
     /// - Config filenames trigger creation of new fragments with the current title
@@ -137,15 +148,30 @@ impl Play {
         for line in &script_lines {
             self.add_config(line, config);
         }
+
+        // Config files named inside a bundle are members of the same bundle
+        for (is_scene_title, text) in config.iter_mut() {
+            if !*is_scene_title {
+                *text = sibling_path(script_filename, text);
+            }
+        }
         
         Ok(())
     }
 
     /// Main entry point that:
-    /// - Reads the script configuration file
+    /// - Reads the script configuration file (or a JSON play model, see model.rs, or a bundle, see bundle.rs)
     /// - Parses it into scene fragments
     /// - Validates that at least one fragment exists and the first has a title
     pub fn prepare(&mut self, script_filename: &String) -> Result<(), u8> {
+        if is_bundle_file(script_filename) {
+            return self.prepare(&script_member(script_filename)?);
+        }
+        if is_model_file(script_filename) {
+            self.fragments = load_play_json(script_filename)?.fragments;
+            return self.check_fragments();
+        }
+
         let mut config: ScriptConfig = Vec::new();
         
         if let Err(error_code) = self.read_config(script_filename, &mut config) {
@@ -156,6 +182,11 @@ impl Play {
             return Err(error_code);
         }
 
+        self.check_fragments()
+    }
+
+    /// Validates that at least one fragment exists and the first has a title
+    pub fn check_fragments(&self) -> Result<(), u8> {
         if self.fragments.is_empty() {
             writeln!(std::io::stderr().lock(), "Error: No scene fragments were created").expect("Failed to write to stderr");
             return Err(SCRIPT_PARSING_ERROR);
@@ -180,19 +211,95 @@ impl Play {
         Ok(())
     }
 
-    ///  Executes the play:
+    pub fn fragments(&self) -> &Fragments {
+        &self.fragments
+    }
+
+    /// The entrances before and exits after every fragment, exactly as recite() would perform them
+    pub fn cast_changes(&self) -> Result<Vec<CastChange>, u8> {
+        let mut guards: Vec<MutexGuard<SceneFragment>> = Vec::new();
+        for (i, fragment_arc) in self.fragments.iter().enumerate() {
+            match fragment_arc.lock() {
+                Ok(guard) => guards.push(guard),
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
+                        .expect("Failed to write to stderr");
+                    return Err(SCRIPT_PARSING_ERROR);
+                }
+            }
+        }
+
+        let mut changes: Vec<CastChange> = Vec::new();
+        for i in 0..guards.len() {
+            let mut entrances: Vec<ScriptEvent> = Vec::new();
+            let mut exits: Vec<ScriptEvent> = Vec::new();
+            if i == 0 {
+                guards[i].enter_all(&mut entrances);
+            } else {
+                guards[i].enter(&guards[i - 1], &mut entrances);
+            }
+            if i == guards.len() - 1 {
+                guards[i].exit_all(&mut exits);
+            } else {
+                guards[i].exit(&guards[i + 1], &mut exits);
+            }
+            let names = |events: Vec<ScriptEvent>| -> Vec<String> {
+                events.into_iter().filter_map(|event| match event {
+                    ScriptEvent::Enter(name) | ScriptEvent::Exit(name) => Some(name),
+                    _ => None,
+                }).collect()
+            };
+            changes.push((names(entrances), names(exits)));
+        }
+        Ok(changes)
+    }
+
+    /// Every distinct character in the play, in order of first entrance
+    pub fn characters(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for (i, fragment_arc) in self.fragments.iter().enumerate() {
+            match fragment_arc.lock() {
+                Ok(ref fragment) => {
+                    for name in fragment.player_names() {
+                        if !names.contains(&name) {
+                            names.push(name);
+                        }
+                    }
+                }
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
+                        .expect("Failed to write to stderr");
+                }
+            }
+        }
+        names
+    }
+
+    ///  Executes the play on the given stage:
+    /// - Announces each fragment's title
     /// - Handles player entrances 
     /// - Each fragment recites its lines
     /// - Handles player exits 
-    pub fn recite(&mut self) {
+    pub fn recite(&mut self, stage: &mut dyn Stage) {
         let num_fragments = self.fragments.len();
         
         for i in 0..num_fragments {
+            match self.fragments[i].lock() {
+                Ok(ref fragment) => {
+                    fragment.print_title(i, stage);
+                }
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
+                        .expect("Failed to write to stderr");
+                    continue;
+                }
+            }
+
             if i == 0 {
                 // First fragment
                 match self.fragments[i].lock() {
                     Ok(ref mut fragment) => {
-                        fragment.enter_all();
+                        fragment.enter_all(stage);
                     }
                     Err(_) => {
                         writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
@@ -206,7 +313,7 @@ impl Play {
                     Ok(ref prev_fragment) => {
                         match self.fragments[i].lock() {
                             Ok(ref mut curr_fragment) => {
-                                curr_fragment.enter(&prev_fragment);
+                                curr_fragment.enter(prev_fragment, stage);
                             }
                             Err(_) => {
                                 writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
@@ -225,21 +332,19 @@ impl Play {
             
             match self.fragments[i].lock() {
                 Ok(ref mut fragment) => {
-                    fragment.recite();
+                    fragment.recite(stage);
                 }
                 Err(_) => {
                     writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {} for recite", i)
                         .expect("Failed to write to stderr");
                 }
             }
-            
-            writeln!(std::io::stdout().lock()).expect("Failed to write to stdout");
 
             if i == num_fragments - 1 {
                 // Final fragment
                 match self.fragments[i].lock() {
                     Ok(ref fragment) => {
-                        fragment.exit_all();
+                        fragment.exit_all(stage);
                     }
                     Err(_) => {
                         writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
@@ -252,7 +357,7 @@ impl Play {
                     Ok(ref next_fragment) => {
                         match self.fragments[i].lock() {
                             Ok(ref curr_fragment) => {
-                                curr_fragment.exit(&next_fragment);
+                                curr_fragment.exit(next_fragment, stage);
                             }
                             Err(_) => {
                                 let _ = writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
@@ -2,16 +2,25 @@
 /// Author: Zichu Pan, Edgar Palomino
 /// Summary: This module implements the Player structure that represents individual actors/characters in a play, 
 /// managing their dialogue lines and delivery.
+use std::collections::HashMap;
 use std::sync::atomic::Ordering;
 use super::declarations::WHINGE_MODE;
 use super::script_gen::grab_trimmed_file_lines;
+use super::stage::{Stage, ScriptEvent};
+use super::pace::{is_valid_pause, MAX_PAUSE};
 use std::io::Write;
 
 pub type PlayLines = Vec<(usize, String)>; // (line_number, line_text)
 
+pub const PAUSE_ANNOTATION_START: &str = "[pause ";
+pub const PAUSE_ANNOTATION_END: &str = "]";
+
 pub struct Player {
     name: String,
+    source: String,     // part file the lines were read from
     lines: PlayLines,
+    source_lines: HashMap<usize, usize>, // line_number -> line of the part file it was read from
+    pauses: HashMap<usize, f64>, // line_number -> seconds to pause after that line
     index: usize,
 }
 
@@ -20,18 +29,30 @@ impl Player {
     pub fn new(name: &String) -> Player {
         Player {
             name: name.clone(),
+            source: String::new(),
             lines: PlayLines::new(),
+            source_lines: HashMap::new(),
+            pauses: HashMap::new(),
             index: 0,
         }
     }
 
+    /// Creates a player whose lines are already known (e.g. received from another process)
+    pub fn from_lines(name: &String, lines: PlayLines) -> Player {
+        let mut player = Player::new(name);
+        player.lines = lines;
+        player.lines.sort();
+        player
+    }
+
     /// Parses individual script lines:
     /// - Expects format: <line_number> <dialogue_text>
     /// - Extracts line number from first token
-    /// - Stores the remaining text as dialogue
+    /// - Stores the remaining text as dialogue, minus any trailing [pause <seconds>] annotation
     /// - Warns about invalid line numbers in whinge mode
     /// - Ignores empty lines
-    fn add_script_line(&mut self, line: &String) {
+    /// - Remembers which line of the part file each line came from
+    fn add_script_line(&mut self, line: &String, source_line: usize) {
         // Ignore empty lines
         if line.is_empty() {
             return;
@@ -43,7 +64,9 @@ impl Player {
                 // Try to parse the first token as line number
                 match first_token.parse::<usize>() {
                     Ok(line_number) => {
-                        self.lines.push((line_number, trimmed_rest.to_string()));
+                        let text = self.take_pause_annotation(line_number, trimmed_rest);
+                        self.lines.push((line_number, text.to_string()));
+                        self.source_lines.insert(line_number, source_line);
                     }
                     Err(_error_code) => {
                         if WHINGE_MODE.load(Ordering::SeqCst) {
@@ -55,20 +78,42 @@ impl Player {
             }
     }
 
+    /// Strips a trailing [pause <seconds>] annotation from the line text, remembering the pause
+    fn take_pause_annotation<'a>(&mut self, line_number: usize, text: &'a str) -> &'a str {
+        if let Some(start) = text.rfind(PAUSE_ANNOTATION_START) {
+            if let Some(seconds) = text[start + PAUSE_ANNOTATION_START.len()..].strip_suffix(PAUSE_ANNOTATION_END) {
+                match seconds.trim().parse::<f64>() {
+                    Ok(seconds) if is_valid_pause(seconds) => {
+                        self.pauses.insert(line_number, seconds);
+                        return text[..start].trim_end();
+                    }
+                    _ => {
+                        if WHINGE_MODE.load(Ordering::SeqCst) {
+                            writeln!(std::io::stderr().lock(), "Warning: '{}' is not a valid pause (0 to {} seconds)",
+                                     seconds, MAX_PAUSE).expect("Failed to write to stderr");
+                        }
+                    }
+                }
+            }
+        }
+        text
+    }
+
     /// Loads the player's script:
     /// - Reads lines from the character's script file
     /// - Parses each line using add_script_line()
     /// - Sorts lines by line number to handle out-of-order input
     pub fn prepare(&mut self, part_filename: &String) {
         let mut part_lines: Vec<String> = Vec::new();
+        self.source = part_filename.clone();
     
         if let Err(error_code) = grab_trimmed_file_lines(part_filename, &mut part_lines) {
             std::panic::panic_any(error_code);
         }
 
         // Process each line and add to player's lines
-        for line in &part_lines {
-            self.add_script_line(line);
+        for (i, line) in part_lines.iter().enumerate() {
+            self.add_script_line(line, i + 1);
         }
 
         // Sort lines by line number to handle out-of-order lines
@@ -78,10 +123,10 @@ impl Player {
 
     /// Delivers the next line of dialogue:
     /// - Checks if all lines have been spoken
-    /// - Prints character name if speaker changes
-    /// - Prints the dialogue text
+    /// - Announces the character name on the stage if speaker changes
+    /// - Performs the numbered dialogue text
     /// - Advances the index to next line
-    pub fn speak(&mut self, current_speaker: &mut String) {
+    pub fn speak(&mut self, current_speaker: &mut String, stage: &mut dyn Stage) {
         // return if all lines have already been spoken
         if self.index >= self.lines.len() {
             return;
@@ -91,14 +136,22 @@ impl Player {
         if *current_speaker != self.name {
             // Update the current speaker to this player's name
             *current_speaker = self.name.clone();
-            writeln!(std::io::stdout().lock()).expect("Failed to write to stdout");
-            writeln!(std::io::stdout().lock(), "{}.", self.name).expect("Failed to write to stdout");
+            stage.perform(ScriptEvent::Speaker(self.name.clone()));
         }
 
-        writeln!(std::io::stdout().lock(), "{}", self.lines[self.index].1).expect("Failed to write to stdout");
+        let (line_number, text) = &self.lines[self.index];
+        stage.perform(ScriptEvent::Line(*line_number, text.clone()));
+        if let Some(seconds) = self.pauses.get(line_number) {
+            stage.perform(ScriptEvent::Pause(*seconds));
+        }
         self.index += 1;
     }
 
+    /// Takes back the last line spoken, so that it is spoken again next
+    pub fn rewind(&mut self) {
+        self.index = self.index.saturating_sub(1);
+    }
+
     pub fn next_line(&self) -> Option<usize> {
         if self.index < self.lines.len() {
             Some(self.lines[self.index].0)
@@ -110,7 +163,50 @@ impl Player {
     pub fn name(&self) -> &String {
         &self.name
     }
-    
+
+    pub fn lines(&self) -> &PlayLines {
+        &self.lines
+    }
+
+    pub fn source(&self) -> &String {
+        &self.source
+    }
+
+    /// Line of the part file that a line was read from, counting from 1
+    pub fn source_line(&self, line_number: usize) -> Option<usize> {
+        self.source_lines.get(&line_number).copied()
+    }
+
+    pub fn set_source(&mut self, source: &str) {
+        self.source = source.to_string();
+    }
+
+    /// Pause annotations, sorted by line number
+    pub fn pauses(&self) -> Vec<(usize, f64)> {
+        let mut pauses: Vec<(usize, f64)> = self.pauses.iter().map(|(line, seconds)| (*line, *seconds)).collect();
+        pauses.sort_by_key(|(line, _)| *line);
+        pauses
+    }
+
+    pub fn add_pause(&mut self, line_number: usize, seconds: f64) {
+        self.pauses.insert(line_number, seconds);
+    }
+
+    /// A copy of the player, ready to speak from the start, holding only the lines whose numbers pass keep
+    pub fn with_lines_where(&self, keep: impl Fn(usize) -> bool) -> Player {
+        let mut player = Player::new(&self.name);
+        player.source = self.source.clone();
+        for (line_number, text) in self.lines.iter().filter(|(line_number, _)| keep(*line_number)) {
+            player.lines.push((*line_number, text.clone()));
+            if let Some(source_line) = self.source_lines.get(line_number) {
+                player.source_lines.insert(*line_number, *source_line);
+            }
+            if let Some(seconds) = self.pauses.get(line_number) {
+                player.pauses.insert(*line_number, *seconds);
+            }
+        }
+        player
+    }
 }
 
 impl PartialEq for Player {
@@ -142,3 +238,20 @@ impl Ord for Player {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pause_annotations_are_read_and_checked() {
+        let mut player = Player::new(&"A".to_string());
+        player.add_script_line(&"1 Go. [pause 2.5]".to_string(), 1);
+        player.add_script_line(&"2 Stay. [pause inf]".to_string(), 2);
+        player.add_script_line(&"3 Wait. [pause 1e300]".to_string(), 3);
+        assert_eq!(player.lines()[0].1, "Go.");
+        assert_eq!(player.lines()[1].1, "Stay. [pause inf]");
+        assert_eq!(player.lines()[2].1, "Wait. [pause 1e300]");
+        assert_eq!(player.pauses(), vec![(1, 2.5)]);
+    }
+}
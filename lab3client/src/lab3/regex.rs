@@ -0,0 +1,465 @@
+/// regex.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A small backtracking regular expression matcher for searching a play. It supports literals, '.',
+/// bracketed classes with ranges and negation, the \d \w \s classes (and \D \W \S), \b word boundaries,
+/// '^' and '$' anchors, groups, alternation, and the *, +, ? and {m,n} repetitions, which are greedy unless
+/// followed by '?'. Matching can ignore case. A repetition of a single character is matched with a loop, so
+/// long lines do not nest calls, and a search that starts with one skips the starts it has already covered.
+/// Backtracking is still exponential on patterns like (a+)+b, so each search has a budget of steps and of
+/// nesting, and gives up with an error once either runs out.
+use std::cell::Cell;
+
+/// Nodes and characters a single search may try before the pattern is reported as too complex
+pub const MAX_MATCH_STEPS: usize = 1_000_000;
+/// Nodes a search may have open at once, which keeps the calls nested for repetitions of groups off the stack's end
+pub const MAX_MATCH_DEPTH: usize = 2_000;
+
+// One element of a bracketed class
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),    // \d, or \D when true
+    Word(bool),     // \w, or \W when true
+    Space(bool),    // \s, or \S when true
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Literal(char),
+    Any,
+    Class(Vec<ClassItem>, bool),                    // (items, negated)
+    Start,
+    End,
+    WordBoundary,
+    Group(Vec<Vec<Node>>),                          // alternatives, each a sequence
+    Repeat(Box<Node>, usize, Option<usize>, bool),  // (node, min, max, greedy)
+}
+
+#[derive(Clone, Debug)]
+pub struct Regex {
+    alternatives: Vec<Vec<Node>>,
+    ignore_case: bool,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// True if the pattern has an uppercase letter outside an escape, which makes a smart-case search exact
+pub fn has_uppercase(pattern: &str) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.position += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut sequence: Vec<Node> = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let mut node = self.atom()?;
+            while let Some((min, max)) = self.quantifier()? {
+                let greedy = if self.peek() == Some('?') {
+                    self.position += 1;
+                    false
+                } else {
+                    true
+                };
+                node = Node::Repeat(Box::new(node), min, max, greedy);
+            }
+            sequence.push(node);
+        }
+        Ok(sequence)
+    }
+
+    /// Reads a *, +, ? or {m,n} repetition if one comes next
+    fn quantifier(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let quantifier = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                // a brace that does not start a well-formed repetition is a literal
+                let close = match self.chars[self.position..].iter().position(|c| *c == '}') {
+                    Some(offset) => self.position + offset,
+                    None => return Ok(None),
+                };
+                let inside: String = self.chars[self.position + 1..close].iter().collect();
+                let bounds: Vec<&str> = inside.splitn(2, ',').collect();
+                let min = match bounds[0].parse::<usize>() {
+                    Ok(min) => min,
+                    Err(_) => return Ok(None),
+                };
+                let max = match bounds.get(1) {
+                    None => Some(min),
+                    Some(&"") => None,
+                    Some(max) => match max.parse::<usize>() {
+                        Ok(max) if max >= min => Some(max),
+                        _ => return Err(format!("bad repetition {{{}}}", inside)),
+                    },
+                };
+                self.position = close;
+                (min, max)
+            }
+            _ => return Ok(None),
+        };
+        self.position += 1;
+        Ok(Some(quantifier))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                if self.chars[self.position..].starts_with(&['?', ':']) {
+                    self.position += 2;
+                }
+                let alternatives = self.alternatives()?;
+                match self.next() {
+                    Some(')') => Ok(Node::Group(alternatives)),
+                    _ => Err("missing )".to_string()),
+                }
+            }
+            Some(')') => Err("unmatched )".to_string()),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('[') => self.class(),
+            Some('\\') => self.escape(),
+            Some(c @ ('*' | '+' | '?')) => Err(format!("nothing to repeat before {}", c)),
+            Some(c) => Ok(Node::Literal(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    /// The class an escape like \d names, if it names one
+    fn class_escape(c: char) -> Option<ClassItem> {
+        match c {
+            'd' | 'D' => Some(ClassItem::Digit(c == 'D')),
+            'w' | 'W' => Some(ClassItem::Word(c == 'W')),
+            's' | 'S' => Some(ClassItem::Space(c == 'S')),
+            _ => None,
+        }
+    }
+
+    fn escaped_char(c: char) -> char {
+        match c {
+            'n' => '\n',
+            't' => '\t',
+            _ => c,
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('b') => Ok(Node::WordBoundary),
+            Some(c) => match Self::class_escape(c) {
+                Some(item) => Ok(Node::Class(vec![item], false)),
+                None => Ok(Node::Literal(Self::escaped_char(c))),
+            },
+            None => Err("trailing backslash".to_string()),
+        }
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+        let mut items: Vec<ClassItem> = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => break,
+                Some(c) => c,
+                None => return Err("missing ]".to_string()),
+            };
+            first = false;
+            let start = if c == '\\' {
+                let escaped = self.next().ok_or_else(|| "trailing backslash".to_string())?;
+                if let Some(item) = Self::class_escape(escaped) {
+                    items.push(item);
+                    continue;
+                }
+                Self::escaped_char(escaped)
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.position + 1).is_some_and(|c| *c != ']') {
+                self.position += 1;
+                let end = match self.next() {
+                    Some('\\') => Self::escaped_char(self.next().ok_or_else(|| "trailing backslash".to_string())?),
+                    Some(end) => end,
+                    None => return Err("missing ]".to_string()),
+                };
+                if end < start {
+                    return Err(format!("bad range {}-{}", start, end));
+                }
+                items.push(ClassItem::Range(start, end));
+            } else {
+                items.push(ClassItem::Range(start, start));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+}
+
+/// Matches nodes against the characters of one text
+struct Matcher<'a> {
+    text: &'a [char],
+    ignore_case: bool,
+    steps: Cell<usize>,     // nodes and characters tried so far, across every start position
+    depth: Cell<usize>,     // nodes being matched at the moment, each waiting on the ones after it
+}
+
+impl Matcher<'_> {
+    fn exhausted(&self) -> bool {
+        self.steps.get() >= MAX_MATCH_STEPS
+    }
+
+    fn count_steps(&self, steps: usize) {
+        self.steps.set(self.steps.get().saturating_add(steps));
+    }
+
+    /// Whether a node that matches exactly one character (a literal, '.' or a class) matches c
+    fn matches_char(&self, node: &Node, c: char) -> bool {
+        match node {
+            Node::Literal(expected) => self.fold(c) == self.fold(*expected),
+            Node::Any => c != '\n',
+            Node::Class(items, negated) => self.in_class(items, c) != *negated,
+            _ => false,
+        }
+    }
+
+    /// Where a run of up to max matches of a single-character node starting at position ends, or None if the
+    /// node matches something else
+    fn run_end(&self, node: &Node, position: usize, max: Option<usize>) -> Option<usize> {
+        if !matches!(node, Node::Literal(_) | Node::Any | Node::Class(_, _)) {
+            return None;
+        }
+        let limit = max.map_or(self.text.len(), |max| self.text.len().min(position.saturating_add(max)));
+        let mut end = position;
+        while end < limit && self.matches_char(node, self.text[end]) {
+            end += 1;
+        }
+        self.count_steps(end - position + 1);
+        Some(end)
+    }
+
+    fn fold(&self, c: char) -> char {
+        if self.ignore_case {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    }
+
+    fn in_class(&self, items: &[ClassItem], c: char) -> bool {
+        let candidates = if self.ignore_case {
+            vec![c, c.to_lowercase().next().unwrap_or(c), c.to_uppercase().next().unwrap_or(c)]
+        } else {
+            vec![c]
+        };
+        items.iter().any(|item| match item {
+            ClassItem::Range(start, end) => candidates.iter().any(|c| (*start..=*end).contains(c)),
+            ClassItem::Digit(negated) => c.is_ascii_digit() != *negated,
+            ClassItem::Word(negated) => is_word_char(c) != *negated,
+            ClassItem::Space(negated) => c.is_whitespace() != *negated,
+        })
+    }
+
+    /// Matches the sequence at position, calling next with each end position until it accepts one
+    fn sequence(&self, sequence: &[Node], position: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        match sequence.split_first() {
+            None => next(position),
+            Some((node, rest)) => self.node(node, position, &mut |end| self.sequence(rest, end, next)),
+        }
+    }
+
+    fn node(&self, node: &Node, position: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        // once the budget is spent every node fails, which unwinds the search quickly
+        if self.exhausted() {
+            return false;
+        }
+        if self.depth.get() >= MAX_MATCH_DEPTH {
+            self.steps.set(MAX_MATCH_STEPS);
+            return false;
+        }
+        self.count_steps(1);
+        self.depth.set(self.depth.get() + 1);
+        let matched = self.match_node(node, position, next);
+        self.depth.set(self.depth.get() - 1);
+        matched
+    }
+
+    fn match_node(&self, node: &Node, position: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        let current = self.text.get(position).copied();
+        match node {
+            Node::Literal(_) | Node::Any | Node::Class(_, _) => {
+                current.is_some_and(|c| self.matches_char(node, c)) && next(position + 1)
+            }
+            Node::Start => position == 0 && next(position),
+            Node::End => position == self.text.len() && next(position),
+            Node::WordBoundary => {
+                let before = position > 0 && is_word_char(self.text[position - 1]);
+                let after = current.is_some_and(is_word_char);
+                before != after && next(position)
+            }
+            Node::Group(alternatives) => alternatives.iter().any(|sequence| self.sequence(sequence, position, next)),
+            Node::Repeat(node, min, max, greedy) => match self.run_end(node, position, *max) {
+                Some(end) => self.repeat_run(position + min, end, *greedy, next),
+                None => self.repeat(node, *min, *max, *greedy, 0, position, next),
+            },
+        }
+    }
+
+    /// Tries each end of a run of single characters from first to last, longest first when greedy
+    fn repeat_run(&self, first: usize, last: usize, greedy: bool, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        if first > last {
+            return false;
+        }
+        if greedy {
+            (first..=last).rev().any(|end| !self.exhausted() && next(end))
+        } else {
+            (first..=last).any(|end| !self.exhausted() && next(end))
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn repeat(&self, node: &Node, min: usize, max: Option<usize>, greedy: bool, count: usize, position: usize,
+              next: &mut dyn FnMut(usize) -> bool) -> bool {
+        if count >= min && !greedy && next(position) {
+            return true;
+        }
+        if max.is_none_or(|max| count < max) {
+            let matched = self.node(node, position, &mut |end| {
+                // a repetition that matched nothing would repeat forever
+                !(end == position && count >= min)
+                    && self.repeat(node, min, max, greedy, count + 1, end, next)
+            });
+            if matched {
+                return true;
+            }
+        }
+        count >= min && greedy && next(position)
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+        let mut parser = Parser { chars: pattern.chars().collect(), position: 0 };
+        let alternatives = parser.alternatives()?;
+        if parser.position < parser.chars.len() {
+            return Err("unmatched )".to_string());
+        }
+        Ok(Regex { alternatives, ignore_case })
+    }
+
+    /// The byte range of the leftmost match in text, or an error if the search ran out of steps
+    pub fn find(&self, text: &str) -> Result<Option<(usize, usize)>, String> {
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).chain([text.len()]).collect();
+        let matcher = Matcher { text: &chars, ignore_case: self.ignore_case, steps: Cell::new(0), depth: Cell::new(0) };
+        let mut start = 0;
+        while start <= chars.len() {
+            let mut found: Option<usize> = None;
+            let matched = self.alternatives.iter().any(|sequence| {
+                matcher.sequence(sequence, start, &mut |end| {
+                    found = Some(end);
+                    true
+                })
+            });
+            if matcher.exhausted() {
+                return Err("pattern too complex".to_string());
+            }
+            if matched {
+                return Ok(found.map(|end| (offsets[start], offsets[end])));
+            }
+            start = self.next_start(&matcher, start);
+        }
+        Ok(None)
+    }
+
+    /// The next start worth trying after a failed one. When the pattern is a single sequence starting with an
+    /// optional repetition of one character, such as .*, a start inside that repetition's run from the failed
+    /// start would only try ends the failed start already tried.
+    fn next_start(&self, matcher: &Matcher, start: usize) -> usize {
+        if let [sequence] = self.alternatives.as_slice() {
+            if let Some(Node::Repeat(node, 0, None, _)) = sequence.first() {
+                if let Some(end) = matcher.run_end(node, start, None) {
+                    return end + 1;
+                }
+            }
+        }
+        start + 1
+    }
+
+    pub fn is_match(&self, text: &str) -> Result<bool, String> {
+        self.find(text).map(|found| found.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_do_not_nest_calls() {
+        let line = "a".repeat(50000);
+        assert_eq!(Regex::new(".*x", false).unwrap().find(&line), Ok(None));
+        assert_eq!(Regex::new("a*?$", false).unwrap().find(&line), Ok(Some((0, 50000))));
+        assert_eq!(Regex::new("[a-z]+", false).unwrap().find(&line), Ok(Some((0, 50000))));
+        assert!(Regex::new("(a)*x", false).unwrap().find(&line).is_err());
+    }
+
+    #[test]
+    fn ordinary_patterns_stay_within_budget() {
+        let line = format!("{} x", "Words, words, words. ".repeat(50));
+        assert_eq!(Regex::new(".*x", false).unwrap().find(&line), Ok(Some((0, line.len()))));
+        assert_eq!(Regex::new(".*y", false).unwrap().find(&line), Ok(None));
+        assert_eq!(Regex::new("w.*x", false).unwrap().find(&line), Ok(Some((7, line.len()))));
+        assert_eq!(Regex::new("\\bwords?\\b.*\\bx$", true).unwrap().find(&line), Ok(Some((0, line.len()))));
+        assert_eq!(Regex::new("(wor|ds)+\\.", false).unwrap().find(&line), Ok(Some((14, 20))));
+    }
+
+    #[test]
+    fn nested_repetitions_run_out_of_steps() {
+        let regex = Regex::new("(a+)+b", false).unwrap();
+        assert_eq!(regex.find(&"a".repeat(40)), Err("pattern too complex".to_string()));
+        assert_eq!(regex.find("aaab"), Ok(Some((0, 4))));
+        assert_eq!(Regex::new("b+", true).unwrap().find(&"a".repeat(10000)), Ok(None));
+    }
+}
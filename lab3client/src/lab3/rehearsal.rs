@@ -0,0 +1,108 @@
+/// rehearsal.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Implements interactive rehearsal, where a human reads one character's part. Every other character's
+/// lines are recited as usual; when the chosen character is due to speak, the cue is shown and the program waits
+/// for the actor to say (or type) the line and press Enter before revealing the correct text. Typed attempts can
+/// optionally be scored against the correct text.
+use std::io::{BufRead, Write};
+use super::stage::{Stage, ScriptEvent};
+use super::scoring::Scorecard;
+
+pub const CUE_WORDS: usize = 5;     // number of words of the preceding line shown as a cue
+pub const CUE_ELLIPSIS: &str = "...";
+pub const ATTEMPT_PROMPT: &str = "> ";
+
+/// Returns the last few words of a line, prefixed with an ellipsis when words were dropped
+pub fn cue_words(text: &str, count: usize) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() <= count {
+        words.join(" ")
+    } else {
+        format!("{}{}", CUE_ELLIPSIS, words[words.len() - count..].join(" "))
+    }
+}
+
+pub struct RehearsalStage<S: Stage> {
+    inner: S,
+    character: String,
+    speaker: String,
+    cue: String,        // text of the most recently spoken line
+    scene: String,      // title of the current scene, for scoring
+    interactive: bool,  // cleared once stdin is exhausted, after which lines are simply revealed
+    scorecard: Option<Scorecard>,
+}
+
+impl<S: Stage> RehearsalStage<S> {
+    pub fn new(inner: S, character: &str) -> RehearsalStage<S> {
+        RehearsalStage {
+            inner,
+            character: character.to_string(),
+            speaker: String::new(),
+            cue: String::new(),
+            scene: String::new(),
+            interactive: true,
+            scorecard: None,
+        }
+    }
+
+    /// Scores every typed attempt against the revealed line
+    pub fn with_scorecard(mut self) -> RehearsalStage<S> {
+        self.scorecard = Some(Scorecard::new(&self.character));
+        self
+    }
+
+    pub fn scorecard(&self) -> Option<&Scorecard> {
+        self.scorecard.as_ref()
+    }
+
+    /// Shows the cue and reads the actor's attempt at the line, or None once there is no more input
+    fn prompt(&mut self, line_number: usize) -> Option<String> {
+        let mut stdout = std::io::stdout().lock();
+        if self.cue.is_empty() {
+            writeln!(stdout, "[Line {}: you begin]", line_number).expect("Failed to write to stdout");
+        } else {
+            writeln!(stdout, "[Line {}, cue: \"{}\"]", line_number, cue_words(&self.cue, CUE_WORDS))
+                .expect("Failed to write to stdout");
+        }
+        write!(stdout, "{}", ATTEMPT_PROMPT).expect("Failed to write to stdout");
+        stdout.flush().expect("Failed to flush stdout");
+
+        let mut attempt = String::new();
+        match std::io::stdin().lock().read_line(&mut attempt) {
+            Ok(0) | Err(_) => {
+                writeln!(stdout).expect("Failed to write to stdout");
+                None
+            }
+            Ok(_) => Some(attempt.trim().to_string()),
+        }
+    }
+}
+
+impl<S: Stage> Stage for RehearsalStage<S> {
+    fn perform(&mut self, event: ScriptEvent) {
+        match event {
+            ScriptEvent::Fragment(_, ref title) if !title.trim().is_empty() => {
+                self.cue.clear();
+                self.scene = title.clone();
+            }
+            ScriptEvent::Speaker(ref name) => self.speaker = name.clone(),
+            ScriptEvent::Line(line_number, ref text) => {
+                let text = text.clone();
+                if self.interactive && self.speaker == self.character {
+                    let attempt = self.prompt(line_number);
+                    self.cue = text.clone();
+                    self.inner.perform(event);
+                    match (attempt, self.scorecard.as_mut()) {
+                        (Some(attempt), Some(scorecard)) => scorecard.score(&self.scene, &text, &attempt),
+                        (Some(_), None) => {}
+                        (None, _) => self.interactive = false,
+                    }
+                    return;
+                }
+                self.cue = text;
+            }
+            _ => {}
+        }
+        self.inner.perform(event);
+    }
+}
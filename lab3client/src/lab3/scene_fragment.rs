@@ -6,8 +6,11 @@ use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 use std::io::Write;
 use super::player::Player;
+use super::stage::{Stage, ScriptEvent, Step};
 use super::declarations::{WHINGE_MODE, CONFIG_PARSING_ERROR};
 use super::script_gen::grab_trimmed_file_lines;
+use super::bundle::sibling_path;
+use super::numbering::Citation;
 
 pub type PlayConfig = Vec<(String, String)>; // (part_name, part_filename)
       
@@ -18,6 +21,7 @@ pub const CONFIG_LINE_TOKEN_COUNT: usize = 2;
 pub struct SceneFragment {
     title: String,
     players: Vec<Arc<Mutex<Player>>>,
+    citation: Option<Citation>, // where a fragment cut out of a larger play sat in it
 }
 
 impl SceneFragment {
@@ -25,9 +29,20 @@ impl SceneFragment {
         SceneFragment {
             title: title.clone(),
             players: Vec::new(),
+            citation: None,
         }
     }
 
+    /// Creates a fragment from already prepared players, sorted by their first line number
+    pub fn from_players(title: &String, players: Vec<Player>) -> SceneFragment {
+        let mut fragment = SceneFragment::new(title);
+        for player in players {
+            fragment.players.push(Arc::new(Mutex::new(player)));
+        }
+        fragment.players.sort_by(Self::compare_players);
+        fragment
+    }
+
     /// Instantiates Player objects:
     /// - Creates a Player for each character
     /// - Calls prepare() on each player with their script file
@@ -70,7 +85,7 @@ impl SceneFragment {
         }
     }
 
-    fn add_config(&mut self, line: &String, config: &mut PlayConfig) {
+    fn add_config(&mut self, config_filename: &str, line: &String, config: &mut PlayConfig) {
         let tokens: Vec<&str> = line.split_whitespace().collect();
         
         if tokens.len() != CONFIG_LINE_TOKEN_COUNT {
@@ -88,7 +103,7 @@ impl SceneFragment {
         if tokens.len() >= CONFIG_LINE_TOKEN_COUNT {
             config.push((
                 tokens[PART_NAME_INDEX].to_string(),
-                tokens[PART_FILENAME_INDEX].to_string()
+                sibling_path(config_filename, tokens[PART_FILENAME_INDEX])
             ));
         }
     }
@@ -111,7 +126,7 @@ impl SceneFragment {
         }
         
         for line in &config_lines {
-            self.add_config(line, config);
+            self.add_config(config_filename, line, config);
         }
         
         Ok(())
@@ -156,19 +171,45 @@ impl SceneFragment {
         !self.title.trim().is_empty()
     }
 
-    fn print_title(&self, is_first: bool) {
-        if !self.title.trim().is_empty() {
-            if !is_first {
-                // Blank line before scene title (except first)
-                writeln!(std::io::stdout().lock()).expect("Failed to write to stdout"); 
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+
+    pub fn players(&self) -> &Vec<Arc<Mutex<Player>>> {
+        &self.players
+    }
+
+    /// Names of the players in this fragment, in entrance order
+    pub fn player_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for player_arc in &self.players {
+            match player_arc.lock() {
+                Ok(ref player) => names.push(player.name().clone()),
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock player in player_names()")
+                        .expect("Failed to write to stderr");
+                }
             }
-        writeln!(std::io::stdout().lock(), "{}", self.title).expect("Failed to write to stdout"); 
-        writeln!(std::io::stdout().lock()).expect("Failed to write to stdout"); 
+        }
+        names
+    }
+
+    /// Keeps the act, scene and line offset the fragment is cited with, so that it is cited the same
+    /// in a recitation of part of the play
+    pub fn with_citation(mut self, citation: Citation) -> SceneFragment {
+        self.citation = Some(citation);
+        self
+    }
+
+    /// Announces the fragment at the given position in the play (titles are only shown if non-empty)
+    pub fn print_title(&self, index: usize, stage: &mut dyn Stage) {
+        stage.perform(ScriptEvent::Fragment(index, self.title.clone()));
+        if let Some((act, scene, offset)) = self.citation {
+            stage.perform(ScriptEvent::Citation(act, scene, offset));
         }
     }
 
-    pub fn enter(&self, previous: &SceneFragment) {
-        self.print_title(false);
+    pub fn enter(&self, previous: &SceneFragment, stage: &mut dyn Stage) {
         for player_arc in &self.players {
             match player_arc.lock() {
                 Ok(ref player) => {
@@ -180,8 +221,7 @@ impl SceneFragment {
                         }
                     });
                     if !in_previous {
-                        writeln!(std::io::stdout().lock(), "[Enter {}.]", player.name())
-                            .expect("Failed to write to stdout"); 
+                        stage.perform(ScriptEvent::Enter(player.name().clone()));
                     }
                 }
                 Err(_) => {
@@ -192,12 +232,11 @@ impl SceneFragment {
         }
     }
 
-    pub fn enter_all(&self) {    
-        self.print_title(true);
+    pub fn enter_all(&self, stage: &mut dyn Stage) {    
         for player_arc in &self.players {
             match player_arc.lock() {
                 Ok(ref player) => {
-                    writeln!(std::io::stdout().lock(), "[Enter {}.]", player.name()).expect("Failed to write to stdout"); 
+                    stage.perform(ScriptEvent::Enter(player.name().clone()));
                 }
                 Err(_) => {
                     writeln!(std::io::stderr().lock(), "Error: Failed to lock player in enter_all()")
@@ -207,7 +246,7 @@ impl SceneFragment {
         }
     }
 
-    pub fn exit(&self, next: &SceneFragment) {
+    pub fn exit(&self, next: &SceneFragment, stage: &mut dyn Stage) {
         for player_arc in self.players.iter().rev() {
             match player_arc.lock() {
                 Ok(ref player) => {
@@ -219,7 +258,7 @@ impl SceneFragment {
                         }
                     });
                     if !in_next {
-                        writeln!(std::io::stdout().lock(), "[Exit {}.]", player.name()).expect("Failed to write to stdout"); 
+                        stage.perform(ScriptEvent::Exit(player.name().clone()));
                     }
                 }
                 Err(_) => {
@@ -229,11 +268,11 @@ impl SceneFragment {
         }
     }
 
-    pub fn exit_all(&self) {
+    pub fn exit_all(&self, stage: &mut dyn Stage) {
         for player_arc in self.players.iter().rev() {
             match player_arc.lock() {
                 Ok(ref player) => {
-                    writeln!(std::io::stdout().lock(), "[Exit {}.]", player.name()).expect("Failed to write to stdout"); 
+                    stage.perform(ScriptEvent::Exit(player.name().clone()));
                 }
                 Err(_) => {
                     writeln!(std::io::stderr().lock(), "Error: Failed to lock player in exit_all()").expect("Failed to write to stderr"); 
@@ -247,10 +286,13 @@ impl SceneFragment {
     /// - That player speaks their line
     /// - Tracks expected line numbers to detect missing/duplicate lines
     /// - Warns about line number issues in whinge mode
+    /// - Asks the stage after every line whether to go on, go back a line or skip ahead
     /// - Continues until all players have delivered all lines
-    pub fn recite(&mut self) {
+    pub fn recite(&mut self, stage: &mut dyn Stage) {
         let mut current_speaker = String::new();
         let mut expected_line_number: usize = 0;
+        let mut spoken: Vec<usize> = Vec::new();            // player indices in the order they spoke
+        let mut skip: Option<(Step, usize)> = None;         // (step, index of the player speaking when chosen)
         
         loop {
             // Find the player with the smallest next line number
@@ -308,13 +350,43 @@ impl SceneFragment {
             let player_index = next_player_index.unwrap();
             match self.players[player_index].lock() {
                 Ok(ref mut player) => {
-                    player.speak(&mut current_speaker);
+                    player.speak(&mut current_speaker, stage);
                 }
                 Err(_) => {
                     writeln!(std::io::stderr().lock(), "Error: Failed to lock player {} for speaking", player_index)
                         .expect("Failed to write to stderr"); 
                }
             }
+            spoken.push(player_index);
+
+            // Skipping to the next speaker stops at their first line; skipping to the next scene never stops here
+            let skipping = match skip {
+                Some((Step::NextSpeaker, speaker_index)) => speaker_index == player_index,
+                Some((Step::NextScene, _)) => true,
+                _ => false,
+            };
+            if skipping {
+                continue;
+            }
+            skip = None;
+            match stage.step() {
+                Step::Continue => {}
+                Step::Back => {
+                    // Take back the line just spoken and the one before it, so that one is spoken again
+                    for _ in 0..2 {
+                        if let Some(index) = spoken.pop() {
+                            if let Ok(ref mut player) = self.players[index].lock() {
+                                player.rewind();
+                                expected_line_number = player.next_line().unwrap_or(expected_line_number);
+                            }
+                        }
+                    }
+                    current_speaker.clear();
+                }
+                step => skip = Some((step, player_index)),
+            }
         }
+
+        stage.perform(ScriptEvent::EndOfDialogue);
     }
 }
\ No newline at end of file
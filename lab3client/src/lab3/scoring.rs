@@ -0,0 +1,276 @@
+/// scoring.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Scores an actor's attempt at a line against the text from the part file. The comparison is a
+/// word-level edit distance that ignores punctuation and case. Scores are shown as a colored diff, summarized
+/// per scene, and appended to a per-actor history file so accuracy can be followed over time.
+use std::fs::OpenOptions;
+use std::io::{IsTerminal, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::declarations::FAILED_TO_OPEN_FILE;
+use super::script_gen::grab_trimmed_file_lines;
+
+pub const HISTORY_FILE_EXTENSION: &str = ".history";
+pub const HISTORY_SEPARATOR: char = '\t';
+pub const OFF_BOOK_ACCURACY: f64 = 0.9;     // scenes below this accuracy are reported as still on book
+const HISTORY_FIELD_COUNT: usize = 5;       // timestamp, character, lines, accuracy, scene title
+const PERCENT: f64 = 100.0;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// One step in turning the expected words into the attempted words
+#[derive(Clone, Debug, PartialEq)]
+pub enum WordEdit {
+    Same(String),
+    Changed(String, String),    // (expected, attempted)
+    Missing(String),            // expected but not said
+    Extra(String),              // said but not expected
+}
+
+/// Lowercases and strips punctuation, so that "'Tis" and "tis," count as the same word
+pub fn normalize_words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Computes a minimal word-level edit script between the expected and attempted text
+pub fn word_diff(expected: &str, attempt: &str) -> Vec<WordEdit> {
+    let expected_words: Vec<&str> = expected.split_whitespace().collect();
+    let attempt_words: Vec<&str> = attempt.split_whitespace().collect();
+
+    // Words that are pure punctuation normalize to nothing; compare them as themselves
+    let key = |word: &&str| -> String {
+        let normalized = normalize_words(word).concat();
+        if normalized.is_empty() { word.to_string() } else { normalized }
+    };
+    let e: Vec<String> = expected_words.iter().map(key).collect();
+    let a: Vec<String> = attempt_words.iter().map(key).collect();
+
+    // distance[i][j] = edits needed to turn the first i expected words into the first j attempted words
+    let mut distance = vec![vec![0usize; a.len() + 1]; e.len() + 1];
+    for (i, row) in distance.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in distance[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=e.len() {
+        for j in 1..=a.len() {
+            let substitution = distance[i - 1][j - 1] + usize::from(e[i - 1] != a[j - 1]);
+            distance[i][j] = substitution.min(distance[i - 1][j] + 1).min(distance[i][j - 1] + 1);
+        }
+    }
+
+    // Walk back from the end to recover the edits
+    let mut edits: Vec<WordEdit> = Vec::new();
+    let (mut i, mut j) = (e.len(), a.len());
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && distance[i][j] == distance[i - 1][j - 1] + usize::from(e[i - 1] != a[j - 1]) {
+            if e[i - 1] == a[j - 1] {
+                edits.push(WordEdit::Same(expected_words[i - 1].to_string()));
+            } else {
+                edits.push(WordEdit::Changed(expected_words[i - 1].to_string(), attempt_words[j - 1].to_string()));
+            }
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && distance[i][j] == distance[i - 1][j] + 1 {
+            edits.push(WordEdit::Missing(expected_words[i - 1].to_string()));
+            i -= 1;
+        } else {
+            edits.push(WordEdit::Extra(attempt_words[j - 1].to_string()));
+            j -= 1;
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/// Fraction of the expected words that were said correctly, after penalizing extra words
+pub fn accuracy(edits: &[WordEdit]) -> f64 {
+    let expected = edits.iter().filter(|e| !matches!(e, WordEdit::Extra(_))).count();
+    let errors = edits.iter().filter(|e| !matches!(e, WordEdit::Same(_))).count();
+    if expected == 0 {
+        return if errors == 0 { 1.0 } else { 0.0 };
+    }
+    (1.0 - errors as f64 / expected as f64).max(0.0)
+}
+
+/// Renders the edits as one line: missing words in red, wrong words in yellow, extra words in green.
+/// Without color, missing words are shown as [-word-] and extra words as {+word+}.
+pub fn render_diff(edits: &[WordEdit], color: bool) -> String {
+    let mut words: Vec<String> = Vec::new();
+    for edit in edits {
+        words.push(match (edit, color) {
+            (WordEdit::Same(word), _) => word.clone(),
+            (WordEdit::Changed(expected, attempted), true) => format!("{}{}{} {}{}{}", RED, expected, RESET, YELLOW, attempted, RESET),
+            (WordEdit::Changed(expected, attempted), false) => format!("[-{}-] {{+{}+}}", expected, attempted),
+            (WordEdit::Missing(word), true) => format!("{}{}{}", RED, word, RESET),
+            (WordEdit::Missing(word), false) => format!("[-{}-]", word),
+            (WordEdit::Extra(word), true) => format!("{}{}{}", GREEN, word, RESET),
+            (WordEdit::Extra(word), false) => format!("{{+{}+}}", word),
+        });
+    }
+    words.join(" ")
+}
+
+/// Accumulates line scores per scene for one actor's rehearsal
+pub struct Scorecard {
+    character: String,
+    scenes: Vec<(String, usize, f64)>, // (scene_title, lines_scored, total_accuracy)
+    color: bool,
+}
+
+impl Scorecard {
+    pub fn new(character: &str) -> Scorecard {
+        Scorecard {
+            character: character.to_string(),
+            scenes: Vec::new(),
+            color: std::io::stdout().is_terminal(),
+        }
+    }
+
+    /// Scores one attempt, shows the diff and accuracy, and records it under the given scene
+    pub fn score(&mut self, scene_title: &str, expected: &str, attempt: &str) {
+        let edits = word_diff(expected, attempt);
+        let line_accuracy = accuracy(&edits);
+        writeln!(std::io::stdout().lock(), "[{:.0}%] {}", line_accuracy * PERCENT, render_diff(&edits, self.color))
+            .expect("Failed to write to stdout");
+
+        match self.scenes.last_mut() {
+            Some((title, lines, total)) if title == scene_title => {
+                *lines += 1;
+                *total += line_accuracy;
+            }
+            _ => self.scenes.push((scene_title.to_string(), 1, line_accuracy)),
+        }
+    }
+
+    /// Prints the average accuracy of every scene rehearsed
+    pub fn print_summary(&self) {
+        writeln!(std::io::stdout().lock(), "\nScores for {}:", self.character).expect("Failed to write to stdout");
+        for (title, lines, total) in &self.scenes {
+            writeln!(std::io::stdout().lock(), "  {:>3.0}%  {} ({} lines)", total / *lines as f64 * PERCENT, title, lines)
+                .expect("Failed to write to stdout");
+        }
+    }
+
+    /// Appends one record per scene to the history file:
+    /// timestamp <TAB> character <TAB> lines <TAB> accuracy <TAB> scene title
+    pub fn append_history(&self, history_filename: &str) -> Result<(), u8> {
+        let mut file = match OpenOptions::new().create(true).append(true).open(history_filename) {
+            Ok(f) => f,
+            Err(e) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to open history file '{}': {}", history_filename, e)
+                    .expect("Failed to write to stderr");
+                return Err(FAILED_TO_OPEN_FILE);
+            }
+        };
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        for (title, lines, total) in &self.scenes {
+            if let Err(e) = writeln!(file, "{1}{0}{2}{0}{3}{0}{4:.3}{0}{5}", HISTORY_SEPARATOR, timestamp,
+                                     self.character, lines, total / *lines as f64, title) {
+                writeln!(std::io::stderr().lock(), "Error: Failed to write history file '{}': {}", history_filename, e)
+                    .expect("Failed to write to stderr");
+                return Err(FAILED_TO_OPEN_FILE);
+            }
+        }
+        Ok(())
+    }
+
+    /// Default history file for a character: <character>.history in the current directory
+    pub fn default_history_filename(character: &str) -> String {
+        format!("{}{}", character, HISTORY_FILE_EXTENSION)
+    }
+}
+
+/// Reads history files and prints, for every actor and scene, the accuracy of the most recent rehearsal,
+/// marking the scenes that are not yet off book
+pub fn report_history(history_filenames: &[String]) -> Result<(), u8> {
+    // (character, scene_title) -> (timestamp, accuracy) of the latest record
+    let mut latest: Vec<((String, String), (u64, f64))> = Vec::new();
+
+    for filename in history_filenames {
+        let mut lines: Vec<String> = Vec::new();
+        grab_trimmed_file_lines(filename, &mut lines)?;
+        for line in &lines {
+            let fields: Vec<&str> = line.splitn(HISTORY_FIELD_COUNT, HISTORY_SEPARATOR).collect();
+            if fields.len() != HISTORY_FIELD_COUNT {
+                continue;
+            }
+            let (timestamp, accuracy) = match (fields[0].parse::<u64>(), fields[3].parse::<f64>()) {
+                (Ok(t), Ok(a)) => (t, a),
+                _ => continue,
+            };
+            let key = (fields[1].to_string(), fields[4].to_string());
+            match latest.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, record)) => {
+                    if timestamp >= record.0 {
+                        *record = (timestamp, accuracy);
+                    }
+                }
+                None => latest.push((key, (timestamp, accuracy))),
+            }
+        }
+    }
+
+    for ((character, title), (_, accuracy)) in &latest {
+        let status = if *accuracy >= OFF_BOOK_ACCURACY { "off book" } else { "still on book" };
+        writeln!(std::io::stdout().lock(), "{:<16} {:>3.0}%  {:<13}  {}", character, accuracy * PERCENT, status, title)
+            .expect("Failed to write to stdout");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(word: &str) -> WordEdit {
+        WordEdit::Same(word.to_string())
+    }
+
+    #[test]
+    fn exact_match_scores_full_marks() {
+        let edits = word_diff("'Tis now struck twelve.", "tis now STRUCK twelve");
+        assert_eq!(edits, vec![same("'Tis"), same("now"), same("struck"), same("twelve.")]);
+        assert_eq!(accuracy(&edits), 1.0);
+    }
+
+    #[test]
+    fn one_substitution_costs_one_word() {
+        let edits = word_diff("Get thee to a nunnery", "Get thee to a convent");
+        assert_eq!(edits.iter().filter(|edit| !matches!(edit, WordEdit::Same(_))).collect::<Vec<_>>(),
+                   vec![&WordEdit::Changed("nunnery".to_string(), "convent".to_string())]);
+        assert_eq!(accuracy(&edits), 0.8);
+        assert_eq!(render_diff(&edits, false), "Get thee to a [-nunnery-] {+convent+}");
+    }
+
+    #[test]
+    fn insertions_and_deletions() {
+        let missing = word_diff("to be or not to be", "to be or to be");
+        assert_eq!(missing.iter().filter(|edit| !matches!(edit, WordEdit::Same(_))).collect::<Vec<_>>(),
+                   vec![&WordEdit::Missing("not".to_string())]);
+        assert!((accuracy(&missing) - 5.0 / 6.0).abs() < 1e-9);
+
+        let extra = word_diff("to be or not", "well to be or not");
+        assert_eq!(extra[0], WordEdit::Extra("well".to_string()));
+        assert_eq!(extra.len(), 5);
+        assert_eq!(accuracy(&extra), 0.75);
+
+        // more mistakes than words does not go below zero
+        assert_eq!(accuracy(&word_diff("aye", "no no no")), 0.0);
+    }
+
+    #[test]
+    fn empty_input_does_not_divide_by_zero() {
+        assert_eq!(word_diff("", ""), vec![]);
+        assert_eq!(accuracy(&word_diff("", "")), 1.0);
+        assert_eq!(accuracy(&word_diff("", "something")), 0.0);
+        assert_eq!(accuracy(&word_diff("something said", "")), 0.0);
+        assert_eq!(accuracy(&[]), 1.0);
+    }
+}
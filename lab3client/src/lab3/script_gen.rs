@@ -3,66 +3,109 @@
 use std::fs::File;
 use std::io::BufReader;
 use std::io::BufRead;
+use std::io::{Cursor, Read};
 use std::io::Write;
 use std::net::TcpStream;
 
 use super::declarations::{FAILED_TO_OPEN_FILE, FAILED_TO_READ_LINE_FROM_FILE};
+use super::bundle::{split_member, open_member};
+use super::tar::{is_tar_archive, find_member};
 
-pub fn get_buffered_reader(source: &String) -> Result<Box<dyn BufRead>, u8> {
-    // Check if it's a network path: "net:IP:PORT:filename"
-    if source.starts_with("net:") {
-        // net:127.0.0.1:7777:filename.txt
-        let after_net = &source[4..];
-        // "127.0.0.1:7777:filename.txt"
-        // Split into IP, PORT, and filename
-        let parts: Vec<&str> = after_net.splitn(3, ':').collect();
-        
-        if parts.len() < 3 {
-            let _ = writeln!(std::io::stderr().lock(), 
-                "Error: Invalid network path format: '{}' (expected net:IP:PORT:filename)", source);
-            return Err(FAILED_TO_OPEN_FILE);
+pub const NETWORK_PREFIX: &str = "net:";
+
+/// Reads one member out of a tar archive
+fn open_tar_member(archive: &str, member: &str) -> Result<Box<dyn BufRead>, u8> {
+    let mut reader = get_buffered_reader(&archive.to_string())?;
+    let mut bytes: Vec<u8> = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut bytes) {
+        let _ = writeln!(std::io::stderr().lock(), "Error: Failed to read archive '{}': {}", archive, e);
+        return Err(FAILED_TO_READ_LINE_FROM_FILE);
+    }
+    match find_member(&bytes, member) {
+        Ok(Some(contents)) => Ok(Box::new(Cursor::new(contents))),
+        Ok(None) => {
+            let _ = writeln!(std::io::stderr().lock(), "Error: '{}' has no member '{}'", archive, member);
+            Err(FAILED_TO_OPEN_FILE)
         }
-        
-        let ip = parts[0];
-        let port = parts[1];
-        let filename = parts[2];
-        let address = format!("{}:{}", ip, port);
-        
-        // Connect to the server
-        let mut stream = match TcpStream::connect(&address) {
-            Ok(s) => s,
-            Err(e) => {
-                let _ = writeln!(std::io::stderr().lock(), 
-                    "Error: Failed to connect to '{}': {}", address, e);
-                return Err(FAILED_TO_OPEN_FILE);
-            }
+        Err(message) => {
+            let _ = writeln!(std::io::stderr().lock(), "Error: Invalid archive '{}': {}", archive, message);
+            Err(FAILED_TO_OPEN_FILE)
+        }
+    }
+}
+
+pub fn get_buffered_reader(source: &String) -> Result<Box<dyn BufRead>, u8> {
+    // Check if it's a network path: "net:IP:PORT:filename". Tar members on a server are extracted by the
+    // server, so those requests go out as they are; a bundle member is read out of the fetched bundle.
+    if source.starts_with(NETWORK_PREFIX) {
+        return match split_member(source) {
+            Some((container, member)) if !is_tar_archive(container) => open_member(container, member),
+            _ => open_network_file(source),
         };
-        
-        // Send the filename to the server
-        if let Err(e) = writeln!(stream, "{}", filename) {
+    }
+
+    // Check if it's a member of an archive: "archive.tar!member" or "bundle.play!member"
+    if let Some((container, member)) = split_member(source) {
+        if is_tar_archive(container) {
+            return open_tar_member(container, member);
+        }
+        return open_member(container, member);
+    }
+
+    let file = match File::open(source) {
+        Ok(f) => f,
+        Err(e) => {
             let _ = writeln!(std::io::stderr().lock(), 
-                "Error: Failed to send filename '{}' to server: {}", filename, e);
+                "Error: Failed to open file '{}': {}", source, e);
             return Err(FAILED_TO_OPEN_FILE);
         }
-        if let Err(e) = stream.flush() {
+    };
+    
+    Ok(Box::new(BufReader::new(file)))
+}
+
+/// Requests a file from a server: "net:IP:PORT:filename"
+fn open_network_file(source: &str) -> Result<Box<dyn BufRead>, u8> {
+    // net:127.0.0.1:7777:filename.txt
+    let after_net = &source[NETWORK_PREFIX.len()..];
+    // "127.0.0.1:7777:filename.txt"
+    // Split into IP, PORT, and filename
+    let parts: Vec<&str> = after_net.splitn(3, ':').collect();
+    
+    if parts.len() < 3 {
+        let _ = writeln!(std::io::stderr().lock(), 
+            "Error: Invalid network path format: '{}' (expected net:IP:PORT:filename)", source);
+        return Err(FAILED_TO_OPEN_FILE);
+    }
+    
+    let ip = parts[0];
+    let port = parts[1];
+    let filename = parts[2];
+    let address = format!("{}:{}", ip, port);
+    
+    // Connect to the server
+    let mut stream = match TcpStream::connect(&address) {
+        Ok(s) => s,
+        Err(e) => {
             let _ = writeln!(std::io::stderr().lock(), 
-                "Error: Failed to flush stream: {}", e);
+                "Error: Failed to connect to '{}': {}", address, e);
             return Err(FAILED_TO_OPEN_FILE);
         }
-        
-        Ok(Box::new(BufReader::new(stream)))
-    } else {
-        let file = match File::open(source) {
-            Ok(f) => f,
-            Err(e) => {
-                let _ = writeln!(std::io::stderr().lock(), 
-                    "Error: Failed to open file '{}': {}", source, e);
-                return Err(FAILED_TO_OPEN_FILE);
-            }
-        };
-        
-        Ok(Box::new(BufReader::new(file)))
+    };
+    
+    // Send the filename to the server
+    if let Err(e) = writeln!(stream, "{}", filename) {
+        let _ = writeln!(std::io::stderr().lock(), 
+            "Error: Failed to send filename '{}' to server: {}", filename, e);
+        return Err(FAILED_TO_OPEN_FILE);
     }
+    if let Err(e) = stream.flush() {
+        let _ = writeln!(std::io::stderr().lock(), 
+            "Error: Failed to flush stream: {}", e);
+        return Err(FAILED_TO_OPEN_FILE);
+    }
+    
+    Ok(Box::new(BufReader::new(stream)))
 }
 
 pub fn grab_trimmed_file_lines(filename: &String, lines: &mut Vec<String>) -> Result<(), u8> {
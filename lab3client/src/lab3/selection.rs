@@ -0,0 +1,267 @@
+/// selection.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Picks out part of a play to recite: some of its scenes, a range of line numbers, or only some of its
+/// characters. The selected part is built into a play of its own, so entrances and exits are worked out between
+/// the fragments that are kept rather than those of the whole play, while each kept fragment keeps the act, scene
+/// and line offset it is cited with in the whole play. A scene is a titled fragment together with the untitled
+/// fragments that continue it.
+use std::io::Write;
+use super::declarations::BAD_COMMAND_LINE_ERROR;
+use super::play::Play;
+use super::player::Player;
+use super::scene_fragment::SceneFragment;
+use super::stage::ScriptEvent;
+use super::numbering::{Numbering, LineLabels, Citation};
+
+pub const RANGE_SEPARATOR: char = '-';
+pub const NAME_SEPARATOR: char = ',';
+
+/// What to recite; an empty selection is the whole play
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    pub scenes: Vec<String>,                // scene numbers counting from 1, or words of scene titles
+    pub lines: Option<(usize, usize)>,      // first and last line number of each fragment to keep
+    pub characters: Vec<String>,            // the only characters to keep
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty() && self.lines.is_none() && self.characters.is_empty()
+    }
+
+    /// True if fragments are trimmed down to some of their lines, so that ones left with none are dropped
+    fn trims_lines(&self) -> bool {
+        self.lines.is_some() || !self.characters.is_empty()
+    }
+}
+
+/// Parses a line range such as "10-40", "10-" (to the end), "-40" (from the start) or "12" (one line)
+pub fn parse_line_range(text: &str) -> Option<(usize, usize)> {
+    let bound = |text: &str, open: usize| -> Option<usize> {
+        if text.trim().is_empty() { Some(open) } else { text.trim().parse::<usize>().ok() }
+    };
+    let (first, last) = match text.split_once(RANGE_SEPARATOR) {
+        Some((first, last)) => (bound(first, 1)?, bound(last, usize::MAX)?),
+        None => {
+            let line = text.trim().parse::<usize>().ok()?;
+            (line, line)
+        }
+    };
+    if first == 0 || first > last {
+        return None;
+    }
+    Some((first, last))
+}
+
+/// Splits a comma separated list of character names
+pub fn parse_names(text: &str) -> Vec<String> {
+    text.split(NAME_SEPARATOR).map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect()
+}
+
+fn title_words(title: &str) -> Vec<String> {
+    title.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// True if the selector's words appear together in the title, so "Act II Scene I" does not match "ACT II Scene II"
+fn title_matches(title: &str, selector: &str) -> bool {
+    let wanted = title_words(selector);
+    !wanted.is_empty() && title_words(title).windows(wanted.len()).any(|words| words == wanted.as_slice())
+}
+
+/// The scene each fragment belongs to, counting from 1, and each scene's title
+fn scenes_of(fragment_titles: &[String]) -> (Vec<usize>, Vec<String>) {
+    let mut scene_of: Vec<usize> = Vec::new();
+    let mut titles: Vec<String> = Vec::new();
+    for title in fragment_titles {
+        if !title.trim().is_empty() || titles.is_empty() {
+            titles.push(title.clone());
+        }
+        scene_of.push(titles.len());
+    }
+    (scene_of, titles)
+}
+
+/// The scenes a selector names: a number picks one scene, anything else every scene whose title matches
+fn scenes_named(selector: &str, titles: &[String]) -> Result<Vec<usize>, u8> {
+    if let Ok(number) = selector.trim().parse::<usize>() {
+        if (1..=titles.len()).contains(&number) {
+            return Ok(vec![number]);
+        }
+        writeln!(std::io::stderr().lock(), "Error: There is no scene {} (the play has {} scenes)",
+                 number, titles.len()).expect("Failed to write to stderr");
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+    let scenes: Vec<usize> = titles.iter().enumerate()
+        .filter(|(_, title)| title_matches(title, selector))
+        .map(|(i, _)| i + 1)
+        .collect();
+    if scenes.is_empty() {
+        writeln!(std::io::stderr().lock(), "Error: No scene title matches '{}'", selector)
+            .expect("Failed to write to stderr");
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+    Ok(scenes)
+}
+
+/// The highest line number spoken in a fragment
+fn last_line(fragment: &SceneFragment) -> usize {
+    fragment.players().iter()
+        .filter_map(|player_arc| player_arc.lock().ok().and_then(|player| player.lines().last().map(|(n, _)| *n)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Copies of a fragment's players cut down to the selection, or None if the fragment is lost entirely
+fn select_players(fragment: &SceneFragment, selection: &Selection) -> Option<Vec<Player>> {
+    let (first, last) = selection.lines.unwrap_or((1, usize::MAX));
+    let mut players: Vec<Player> = Vec::new();
+    for player_arc in fragment.players() {
+        match player_arc.lock() {
+            Ok(ref player) => {
+                if !selection.characters.is_empty() && !selection.characters.contains(player.name()) {
+                    continue;
+                }
+                let selected = player.with_lines_where(|line_number| (first..=last).contains(&line_number));
+                // silent players stay on stage only while the whole fragment is kept
+                if !selected.lines().is_empty() || selection.lines.is_none() {
+                    players.push(selected);
+                }
+            }
+            Err(_) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to lock player in select_players()")
+                    .expect("Failed to write to stderr");
+            }
+        }
+    }
+    let speaks = players.iter().any(|player| !player.lines().is_empty());
+    if players.is_empty() || (selection.trims_lines() && !speaks) {
+        return None;
+    }
+    Some(players)
+}
+
+/// Builds a play of just the selected part of the given one
+pub fn select(play: &Play, selection: &Selection) -> Result<Play, u8> {
+    let characters = play.characters();
+    for name in &selection.characters {
+        if !characters.contains(name) {
+            writeln!(std::io::stderr().lock(), "Error: '{}' is not a character in the play", name)
+                .expect("Failed to write to stderr");
+            return Err(BAD_COMMAND_LINE_ERROR);
+        }
+    }
+
+    // titles, and citations worked out over the whole play as a recitation of it would
+    let mut fragment_titles: Vec<String> = Vec::new();
+    let mut citations: Vec<Citation> = Vec::new();
+    let mut labels = LineLabels::new(Numbering::default());
+    for (i, fragment_arc) in play.fragments().iter().enumerate() {
+        let (title, last_line) = match fragment_arc.lock() {
+            Ok(ref fragment) => (fragment.title().clone(), last_line(fragment)),
+            Err(_) => (String::new(), 0),
+        };
+        labels.observe(&ScriptEvent::Fragment(i, title.clone()));
+        citations.push(labels.citation());
+        labels.observe(&ScriptEvent::Line(last_line, String::new()));
+        fragment_titles.push(title);
+    }
+    let (scene_of, titles) = scenes_of(&fragment_titles);
+    let mut scenes: Vec<usize> = Vec::new();
+    for selector in &selection.scenes {
+        scenes.extend(scenes_named(selector, &titles)?);
+    }
+
+    let mut fragments: Vec<SceneFragment> = Vec::new();
+    let mut titled_scene = 0;   // the last scene whose title has been kept
+    for (i, ((fragment_arc, scene), citation)) in play.fragments().iter().zip(scene_of).zip(citations).enumerate() {
+        if !scenes.is_empty() && !scenes.contains(&scene) {
+            continue;
+        }
+        let players = match fragment_arc.lock() {
+            Ok(ref fragment) => select_players(fragment, selection),
+            Err(_) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", i)
+                    .expect("Failed to write to stderr");
+                None
+            }
+        };
+        if let Some(players) = players {
+            // the first fragment kept from a scene carries its title, even if the fragment that had it was dropped
+            let title = if titled_scene == scene { String::new() } else { titles[scene - 1].clone() };
+            titled_scene = scene;
+            fragments.push(SceneFragment::from_players(&title, players).with_citation(citation));
+        }
+    }
+
+    if fragments.is_empty() {
+        writeln!(std::io::stderr().lock(), "Error: Nothing in the play matches the selection")
+            .expect("Failed to write to stderr");
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+    Ok(Play::from_fragments(fragments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one scene in two fragments, the second continuing the first's line citations
+    const NESTED_BUNDLE: &str = "test/test_15/nested.play";
+
+    fn cited_lines(selection: &Selection) -> Vec<(String, String)> {
+        let mut play = Play::new();
+        play.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        if !selection.is_empty() {
+            play = select(&play, selection).unwrap();
+        }
+        let mut events: Vec<ScriptEvent> = Vec::new();
+        play.recite(&mut events);
+
+        let mut labels = LineLabels::new(Numbering { every: 1, citations: true });
+        events.iter().filter_map(|event| {
+            labels.observe(event);
+            match event {
+                ScriptEvent::Line(line_number, text) => Some((text.clone(), labels.label(*line_number).unwrap())),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn citations_do_not_depend_on_the_selection() {
+        let whole = cited_lines(&Selection::default());
+        let selections = [
+            Selection { scenes: vec!["1".to_string()], ..Selection::default() },
+            Selection { lines: Some((3, 4)), ..Selection::default() },
+            Selection { characters: vec!["Ophelia".to_string()], ..Selection::default() },
+            Selection { scenes: vec!["Act II Scene I".to_string()], lines: Some((2, 30)),
+                        characters: vec!["Polonius".to_string()] },
+        ];
+        for selection in &selections {
+            let part = cited_lines(selection);
+            assert!(!part.is_empty(), "{:?} selected nothing", selection);
+            for cited in &part {
+                assert!(whole.contains(cited), "{:?} cites '{}' as {}", selection, cited.0, cited.1);
+            }
+        }
+    }
+
+    #[test]
+    fn line_ranges() {
+        assert_eq!(parse_line_range("10-40"), Some((10, 40)));
+        assert_eq!(parse_line_range("10-"), Some((10, usize::MAX)));
+        assert_eq!(parse_line_range("-40"), Some((1, 40)));
+        assert_eq!(parse_line_range("12"), Some((12, 12)));
+        assert_eq!(parse_line_range("40-10"), None);
+        assert_eq!(parse_line_range("0-3"), None);
+    }
+
+    #[test]
+    fn title_selectors_match_whole_words() {
+        assert!(title_matches("Hamlet ACT II Scene I A room", "Act II Scene I"));
+        assert!(!title_matches("Hamlet ACT II Scene II A room", "Act II Scene I"));
+    }
+}
@@ -0,0 +1,275 @@
+/// sheet.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Exports every line of a play as a spreadsheet (CSV or TSV) and imports such a sheet back. Each row
+/// holds a scene index, scene title, character, line number, text, the part file and line it came from and
+/// any pause after it; characters with no lines in a scene get a row of their own so they still enter. On
+/// import, rows are grouped by scene index and sorted by line number (rows without a number stay after the
+/// row before them), so lines can be reassigned, cut, added or reordered in a spreadsheet. The source
+/// columns are informational: importing writes fresh part files.
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use super::play::Play;
+use super::scene_fragment::SceneFragment;
+use super::import::SceneDraft;
+use super::pace::{is_valid_pause, MAX_PAUSE};
+use super::declarations::{WHINGE_MODE, SCRIPT_PARSING_ERROR};
+
+pub const CSV_DELIMITER: char = ',';
+pub const TSV_DELIMITER: char = '\t';
+pub const COLUMNS: [&str; 8] = ["scene", "title", "character", "line", "text", "source_file", "source_line", "pause"];
+const SCENE_COLUMN: usize = 0;
+const TITLE_COLUMN: usize = 1;
+const CHARACTER_COLUMN: usize = 2;
+const LINE_COLUMN: usize = 3;
+const TEXT_COLUMN: usize = 4;
+const PAUSE_COLUMN: usize = 7;
+const QUOTE: char = '"';
+
+pub type Row = Vec<String>;
+
+/// One row per line of every fragment, in the order the lines are spoken, after a header row
+pub fn sheet_rows(play: &Play) -> Result<Vec<Row>, u8> {
+    let mut rows: Vec<Row> = vec![COLUMNS.iter().map(|c| c.to_string()).collect()];
+    for (index, fragment_arc) in play.fragments().iter().enumerate() {
+        let fragment = match fragment_arc.lock() {
+            Ok(fragment) => fragment,
+            Err(_) => {
+                writeln!(std::io::stderr().lock(), "Error: Failed to lock fragment {}", index)
+                    .expect("Failed to write to stderr");
+                return Err(SCRIPT_PARSING_ERROR);
+            }
+        };
+        let mut fragment_rows: Vec<(usize, Row)> = Vec::new();   // (line_number, row)
+        for player_arc in fragment.players() {
+            let player = match player_arc.lock() {
+                Ok(player) => player,
+                Err(_) => {
+                    writeln!(std::io::stderr().lock(), "Error: Failed to lock player in fragment {}", index)
+                        .expect("Failed to write to stderr");
+                    return Err(SCRIPT_PARSING_ERROR);
+                }
+            };
+            let row = |line: String, text: String, source_line: String, pause: String| -> Row {
+                vec![index.to_string(), fragment.title().clone(), player.name().clone(), line, text,
+                     player.source().clone(), source_line, pause]
+            };
+            if player.lines().is_empty() {
+                fragment_rows.push((0, row(String::new(), String::new(), String::new(), String::new())));
+            }
+            let pauses = player.pauses();
+            for (line_number, text) in player.lines() {
+                let source_line = player.source_line(*line_number).map(|n| n.to_string()).unwrap_or_default();
+                let pause = pauses.iter().find(|(line, _)| line == line_number)
+                    .map(|(_, seconds)| seconds.to_string()).unwrap_or_default();
+                fragment_rows.push((*line_number, row(line_number.to_string(), text.clone(), source_line, pause)));
+            }
+        }
+        fragment_rows.sort_by_key(|(line_number, _)| *line_number);
+        rows.extend(fragment_rows.into_iter().map(|(_, row)| row));
+    }
+    Ok(rows)
+}
+
+/// Quotes a field if it holds the delimiter, a quote or a line break, doubling any quotes
+fn quote_field(field: &str, delimiter: char) -> String {
+    if field.contains(delimiter) || field.contains(QUOTE) || field.contains('\n') || field.contains('\r') {
+        format!("{}{}{}", QUOTE, field.replace(QUOTE, "\"\""), QUOTE)
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn write_sheet(rows: &[Row], delimiter: char, out: &mut dyn Write) -> std::io::Result<()> {
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|field| quote_field(field, delimiter)).collect();
+        writeln!(out, "{}", fields.join(&delimiter.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Splits delimited text into rows of fields, following RFC 4180 quoting; blank lines are skipped
+pub fn parse_rows(text: &str, delimiter: char) -> Result<Vec<Row>, String> {
+    let mut rows: Vec<Row> = Vec::new();
+    let mut row: Row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                QUOTE if chars.peek() == Some(&QUOTE) => {
+                    field.push(QUOTE);
+                    chars.next();
+                }
+                QUOTE => in_quotes = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+        } else if c == QUOTE && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            row.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            row.push(std::mem::take(&mut field));
+            if row.iter().any(|f| !f.is_empty()) {
+                rows.push(std::mem::take(&mut row));
+            }
+            row.clear();
+            line += 1;
+        } else {
+            field.push(c);
+        }
+    }
+    if in_quotes {
+        return Err(format!("unterminated quoted field starting before line {}", line));
+    }
+    row.push(field);
+    if row.iter().any(|f| !f.is_empty()) {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Parses a sheet into a play. The first row is a header if one of its fields is "scene"; its column names
+/// then pick the columns, in any order, and otherwise the columns are in export order.
+pub fn parse_sheet(text: &str, delimiter: char, default_title: &str) -> Result<Play, String> {
+    let mut rows = parse_rows(text, delimiter)?;
+    let mut columns: Vec<Option<usize>> = (0..COLUMNS.len()).map(Some).collect();
+    let has_header = rows.first()
+        .map(|row| row.iter().any(|field| field.trim().eq_ignore_ascii_case(COLUMNS[SCENE_COLUMN])))
+        .unwrap_or(false);
+    if has_header {
+        let header = rows.remove(0);
+        columns = COLUMNS.iter()
+            .map(|name| header.iter().position(|field| field.trim().eq_ignore_ascii_case(name)))
+            .collect();
+        for required in [SCENE_COLUMN, CHARACTER_COLUMN, TEXT_COLUMN] {
+            if columns[required].is_none() {
+                return Err(format!("no '{}' column", COLUMNS[required]));
+            }
+        }
+    }
+    let field = |row: &Row, column: usize| -> String {
+        columns[column].and_then(|i| row.get(i)).map(|f| f.trim().to_string()).unwrap_or_default()
+    };
+
+    // Group rows by scene, remembering each row's place among the scene's lines
+    let mut scenes: BTreeMap<usize, Vec<(usize, Row)>> = BTreeMap::new();
+    let mut last_keys: BTreeMap<usize, usize> = BTreeMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        let row_number = i + 1 + has_header as usize;
+        let scene = match field(row, SCENE_COLUMN).parse::<usize>() {
+            Ok(scene) => scene,
+            Err(_) => {
+                if WHINGE_MODE.load(Ordering::SeqCst) {
+                    writeln!(std::io::stderr().lock(), "Warning: Skipping row {} with no scene index", row_number)
+                        .expect("Failed to write to stderr");
+                }
+                continue;
+            }
+        };
+        let last_key = last_keys.entry(scene).or_insert(0);
+        let key = field(row, LINE_COLUMN).parse::<usize>().unwrap_or(*last_key);
+        *last_key = key;
+        scenes.entry(scene).or_default().push((key, row.clone()));
+    }
+
+    let mut fragments: Vec<SceneFragment> = Vec::new();
+    for (_, mut scene_rows) in scenes {
+        scene_rows.sort_by_key(|(key, _)| *key);    // stable, so unnumbered rows keep their place
+        let title = scene_rows.iter().map(|(_, row)| field(row, TITLE_COLUMN)).find(|t| !t.is_empty());
+        let mut scene = SceneDraft::new(title.unwrap_or_default());
+        for (_, row) in &scene_rows {
+            let character = field(row, CHARACTER_COLUMN);
+            // Part files hold a line per line, so a cell spanning several lines becomes one
+            let text = field(row, TEXT_COLUMN).lines().map(str::trim).collect::<Vec<&str>>().join(" ");
+            if character.is_empty() {
+                continue;
+            }
+            if text.is_empty() {
+                scene.add_player(&character);
+                continue;
+            }
+            let number = scene.add_line(&character, field(row, LINE_COLUMN).parse::<usize>().ok(), &text);
+            let pause = field(row, PAUSE_COLUMN);
+            if pause.is_empty() {
+                continue;
+            }
+            match pause.parse::<f64>() {
+                Ok(seconds) if is_valid_pause(seconds) => scene.add_pause(&character, number, seconds),
+                _ => {
+                    if WHINGE_MODE.load(Ordering::SeqCst) {
+                        writeln!(std::io::stderr().lock(), "Warning: '{}' is not a valid pause (0 to {} seconds) \
+                                 after line {} of {}", pause, MAX_PAUSE, number, character)
+                            .expect("Failed to write to stderr");
+                    }
+                }
+            }
+        }
+        scene.finish(&mut fragments, default_title);
+    }
+
+    if fragments.is_empty() {
+        return Err("no lines found".to_string());
+    }
+    Ok(Play::from_fragments(fragments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NESTED_BUNDLE: &str = "test/test_15/nested.play";
+
+    fn written(rows: &[Row], delimiter: char) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        write_sheet(rows, delimiter, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn rows_round_trip() {
+        let mut play = Play::new();
+        play.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        let mut rows = sheet_rows(&play).unwrap();
+        rows.push(vec!["2".to_string(), "A, \"quoted\" title".to_string(), "B\tC".to_string(), String::new(),
+                       "two\nlines\r\nand a \"quote\"".to_string()]);
+        for delimiter in [CSV_DELIMITER, TSV_DELIMITER] {
+            assert_eq!(parse_rows(&written(&rows, delimiter), delimiter), Ok(rows.clone()));
+        }
+    }
+
+    #[test]
+    fn quoting_follows_rfc_4180() {
+        assert_eq!(parse_rows("\u{feff}a,\"b,\"\"c\"\"\"\r\n\r\n,x\n", CSV_DELIMITER),
+                   Ok(vec![vec!["a".to_string(), "b,\"c\"".to_string()], vec![String::new(), "x".to_string()]]));
+        assert!(parse_rows("a,\"b\nc", CSV_DELIMITER).is_err());
+    }
+
+    fn pauses_of(play: &Play) -> Vec<(usize, f64)> {
+        let fragment = play.fragments()[0].lock().unwrap();
+        let player = fragment.players()[0].lock().unwrap();
+        player.pauses()
+    }
+
+    #[test]
+    fn only_finite_bounded_pauses_are_imported() {
+        let sheet = "scene,title,character,line,text,pause\n\
+                     0,T,A,1,Hello,inf\n\
+                     0,T,A,2,There,1e300\n\
+                     0,T,A,3,Go,NaN\n\
+                     0,T,A,4,Now,2.5\n";
+        let play = parse_sheet(sheet, CSV_DELIMITER, "T").unwrap();
+        assert_eq!(pauses_of(&play), vec![(4, 2.5)]);
+    }
+}
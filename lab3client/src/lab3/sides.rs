@@ -0,0 +1,128 @@
+/// sides.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Generates cue scripts ("sides"): for each character, every one of their speeches grouped by scene
+/// title, each preceded by the last few words of the line spoken just before it.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use super::stage::{Stage, ScriptEvent};
+use super::rehearsal::{cue_words, CUE_WORDS};
+use super::declarations::FAILED_TO_OPEN_FILE;
+
+pub const SIDES_FILE_SUFFIX: &str = "_sides.txt";
+pub const OPENING_CUE: &str = "[you begin the scene]";
+
+/// One speech in a cue script: the cue, then the character's numbered lines
+pub type Speech = (String, Vec<(usize, String)>);    // (cue, [(line_number, line_text)])
+pub type SceneSides = Vec<(String, Vec<Speech>)>;    // [(scene_title, speeches)]
+
+/// Collects the sides of every character while the play is recited
+pub struct SidesStage {
+    sides: HashMap<String, SceneSides>,
+    order: Vec<String>,     // characters in order of first speech
+    scene: String,
+    speaker: String,
+    cue: String,            // cue for the speech in progress
+    new_speech: bool,       // set at each speaker change
+    last_line: String,
+}
+
+impl Default for SidesStage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SidesStage {
+    pub fn new() -> SidesStage {
+        SidesStage {
+            sides: HashMap::new(),
+            order: Vec::new(),
+            scene: String::new(),
+            speaker: String::new(),
+            cue: String::new(),
+            new_speech: false,
+            last_line: String::new(),
+        }
+    }
+
+    pub fn characters(&self) -> &Vec<String> {
+        &self.order
+    }
+
+    /// Writes one character's cue script
+    pub fn write_sides(&self, character: &str, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "Sides for {}", character)?;
+        let scenes = match self.sides.get(character) {
+            Some(scenes) => scenes,
+            None => return Ok(()),
+        };
+        for (title, speeches) in scenes {
+            writeln!(out)?;
+            writeln!(out, "{}", title)?;
+            for (cue, lines) in speeches {
+                writeln!(out)?;
+                writeln!(out, "    {}", cue)?;
+                for (line_number, text) in lines {
+                    writeln!(out, "{} {}", line_number, text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes <directory>/<character>_sides.txt for every character
+    pub fn write_all(&self, directory: &str) -> Result<(), u8> {
+        for character in &self.order {
+            let path = Path::new(directory).join(format!("{}{}", character, SIDES_FILE_SUFFIX));
+            let result = File::create(&path).and_then(|mut file| self.write_sides(character, &mut file));
+            if let Err(e) = result {
+                writeln!(std::io::stderr().lock(), "Error: Failed to write '{}': {}", path.display(), e)
+                    .expect("Failed to write to stderr");
+                return Err(FAILED_TO_OPEN_FILE);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Stage for SidesStage {
+    fn perform(&mut self, event: ScriptEvent) {
+        match event {
+            ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => {
+                self.scene = title;
+                self.last_line.clear();
+            }
+            ScriptEvent::Speaker(name) => {
+                self.cue = if self.last_line.is_empty() {
+                    OPENING_CUE.to_string()
+                } else {
+                    cue_words(&self.last_line, CUE_WORDS)
+                };
+                self.speaker = name;
+                self.new_speech = true;
+            }
+            ScriptEvent::Line(line_number, text) => {
+                if !self.order.contains(&self.speaker) {
+                    self.order.push(self.speaker.clone());
+                }
+                let scenes = self.sides.entry(self.speaker.clone()).or_default();
+                if scenes.last().map(|(title, _)| title != &self.scene).unwrap_or(true) {
+                    scenes.push((self.scene.clone(), Vec::new()));
+                }
+                if let Some((_, speeches)) = scenes.last_mut() {
+                    if self.new_speech || speeches.is_empty() {
+                        speeches.push((self.cue.clone(), Vec::new()));
+                    }
+                    if let Some((_, lines)) = speeches.last_mut() {
+                        lines.push((line_number, text.clone()));
+                    }
+                }
+                self.new_speech = false;
+                self.last_line = text;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,152 @@
+/// ssml.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Writes a recitation as SSML documents for offline speech synthesis, one per scene fragment. A
+/// casting file maps characters to voice names, one "<character_name> <voice_name>" pair per line like a
+/// scene config; the name [narrator] casts the voice that reads scene titles and stage directions, which
+/// are skipped when no narrator is cast. Speaker changes and annotated pauses become <break> elements.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use super::stage::{ScriptEvent, fragment_events};
+use super::play::Play;
+use super::pace::PaceSettings;
+use super::html::escape_html;
+use super::options::RecitalOptions;
+use super::script_gen::grab_trimmed_file_lines;
+use super::declarations::{WHINGE_MODE, CONFIG_PARSING_ERROR, FAILED_TO_WRITE_OUTPUT};
+
+pub const NARRATOR: &str = "[narrator]";
+pub const SSML_FILE_EXTENSION: &str = ".ssml";
+const CASTING_LINE_TOKEN_COUNT: usize = 2;
+const MILLISECONDS_PER_SECOND: f64 = 1000.0;
+
+pub type Casting = HashMap<String, String>;     // character name -> voice name
+
+/// Reads a casting file, warning in whinge mode about lines that are not a name and a voice
+pub fn read_casting(casting_filename: &String) -> Result<Casting, u8> {
+    let mut lines: Vec<String> = Vec::new();
+    grab_trimmed_file_lines(casting_filename, &mut lines)?;
+    if lines.is_empty() {
+        writeln!(std::io::stderr().lock(), "Error: Casting file '{}' contains no lines", casting_filename)
+            .expect("Failed to write to stderr");
+        return Err(CONFIG_PARSING_ERROR);
+    }
+
+    let mut casting: Casting = HashMap::new();
+    for line in &lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() == CASTING_LINE_TOKEN_COUNT {
+            casting.insert(tokens[0].to_string(), tokens[1].to_string());
+        } else if WHINGE_MODE.load(Ordering::SeqCst) {
+            writeln!(std::io::stderr().lock(), "Warning: Casting line should be a name and a voice: '{}'", line)
+                .expect("Failed to write to stderr");
+        }
+    }
+    Ok(casting)
+}
+
+fn write_break(seconds: f64, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "<break time=\"{}ms\"/>", (seconds * MILLISECONDS_PER_SECOND).round() as u64)
+}
+
+/// A sentence spoken in the given voice
+fn write_voiced(voice: &str, text: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "<voice name=\"{}\"><s>{}</s></voice>", escape_html(voice), escape_html(text))
+}
+
+/// One fragment's events, from its Fragment event up to the next, as an SSML document
+fn write_document(events: &[ScriptEvent], casting: &Casting, pace: &PaceSettings,
+                  out: &mut dyn Write) -> std::io::Result<()> {
+    let narrator = casting.get(NARRATOR);
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<speak version=\"1.1\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"en-US\">")?;
+
+    let mut speech_end: Option<&str> = None;    // closing tags of the speech in progress
+    for event in events {
+        if !matches!(event, ScriptEvent::Line(_, _) | ScriptEvent::Pause(_)) {
+            if let Some(end) = speech_end.take() {
+                writeln!(out, "{}", end)?;
+            }
+        }
+        match event {
+            ScriptEvent::Fragment(_, title) if !title.trim().is_empty() => {
+                if let Some(narrator) = narrator {
+                    write_voiced(narrator, title, out)?;
+                    write_break(pace.direction_pause, out)?;
+                }
+            }
+            ScriptEvent::Enter(name) | ScriptEvent::Exit(name) => {
+                if let Some(narrator) = narrator {
+                    let verb = if matches!(event, ScriptEvent::Enter(_)) { "Enter" } else { "Exit" };
+                    write_voiced(narrator, &format!("{} {}.", verb, name), out)?;
+                }
+            }
+            ScriptEvent::Speaker(name) => {
+                write_break(pace.speaker_pause, out)?;
+                speech_end = match casting.get(name) {
+                    Some(voice) => {
+                        writeln!(out, "<p><voice name=\"{}\">", escape_html(voice))?;
+                        Some("</voice></p>")
+                    }
+                    None => {
+                        writeln!(out, "<p>")?;
+                        Some("</p>")
+                    }
+                };
+            }
+            ScriptEvent::Line(_, text) => writeln!(out, "<s>{}</s>", escape_html(text))?,
+            ScriptEvent::Pause(seconds) => write_break(*seconds, out)?,
+            _ => {}
+        }
+    }
+    if let Some(end) = speech_end {
+        writeln!(out, "{}", end)?;
+    }
+    writeln!(out, "</speak>")
+}
+
+/// Recites the play and writes <directory>/fragment-<index>.ssml for every fragment, into the --output
+/// directory or the current one
+pub fn export_ssml(play: &mut Play, options: &RecitalOptions) -> Result<(), u8> {
+    let casting = match options.casting {
+        Some(ref casting_filename) => read_casting(casting_filename)?,
+        None => Casting::new(),
+    };
+    let pace = options.pace.clone().unwrap_or_default();
+    let directory = options.output.as_deref().unwrap_or(".");
+
+    let mut events: Vec<ScriptEvent> = Vec::new();
+    play.recite(&mut events);
+
+    let mut warned: Vec<&String> = Vec::new();
+    for event in &events {
+        if let ScriptEvent::Speaker(name) = event {
+            if !casting.contains_key(name) && !warned.contains(&name) && WHINGE_MODE.load(Ordering::SeqCst) {
+                writeln!(std::io::stderr().lock(), "Warning: No voice cast for '{}', using the default voice", name)
+                    .expect("Failed to write to stderr");
+                warned.push(name);
+            }
+        }
+    }
+
+    for fragment in fragment_events(&events) {
+        let index = match fragment.first() {
+            Some(ScriptEvent::Fragment(index, _)) => *index,
+            _ => continue,
+        };
+        let path = Path::new(directory).join(format!("fragment-{}{}", index, SSML_FILE_EXTENSION));
+        let result = File::create(&path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write_document(fragment, &casting, &pace, &mut out)?;
+            out.flush()
+        });
+        if let Err(e) = result {
+            writeln!(std::io::stderr().lock(), "Error: Failed to write '{}': {}", path.display(), e)
+                .expect("Failed to write to stderr");
+            return Err(FAILED_TO_WRITE_OUTPUT);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+/// stage.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Defines the events produced while a play is recited and the Stage trait that receives them,
+/// along with the plain-text stage that reproduces the original stdout format.
+use std::io::Write;
+use super::numbering::{Numbering, LineLabels};
+
+/// One unit of recited output, in the order it is performed
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptEvent {
+    Fragment(usize, String),    // (fragment_index, title) - title may be empty
+    Citation(usize, usize, usize), // (act, scene, line_offset) of a fragment cut out of a larger play
+    Enter(String),              // character entering
+    Exit(String),               // character exiting
+    Speaker(String),            // speaker changed to this character
+    Line(usize, String),        // (line_number, line_text)
+    Pause(f64),                 // seconds requested by a [pause <seconds>] annotation on the previous line
+    EndOfDialogue,              // all lines of the current fragment have been spoken
+}
+
+/// How a recitation goes on after a spoken line, as chosen by the stage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    Continue,       // on to the next line
+    Back,           // speak the previous line again (within the fragment)
+    NextSpeaker,    // go on without stopping until another character speaks
+    NextScene,      // finish the fragment without stopping
+}
+
+/// Splits a recorded recitation at its Fragment events, one slice per fragment starting with its Fragment
+pub fn fragment_events(events: &[ScriptEvent]) -> Vec<&[ScriptEvent]> {
+    let starts: Vec<usize> = events.iter().enumerate()
+        .filter(|(_, event)| matches!(event, ScriptEvent::Fragment(_, _)))
+        .map(|(position, _)| position)
+        .collect();
+    starts.iter().enumerate()
+        .map(|(n, start)| &events[*start..starts.get(n + 1).copied().unwrap_or(events.len())])
+        .collect()
+}
+
+/// Anything that a play can be performed on: stdout, a network connection, a file exporter, ...
+pub trait Stage {
+    fn perform(&mut self, event: ScriptEvent);
+
+    /// Called after every spoken line; only stages that step through a play do anything but continue
+    fn step(&mut self) -> Step {
+        Step::Continue
+    }
+}
+
+/// Collecting events into a vector lets a recitation be replayed or exported afterwards
+impl Stage for Vec<ScriptEvent> {
+    fn perform(&mut self, event: ScriptEvent) {
+        self.push(event);
+    }
+}
+
+/// Writes the recitation as plain text in the original format:
+/// - Scene titles surrounded by blank lines (no leading blank line for the first fragment)
+/// - [Enter X.] and [Exit X.] directives
+/// - A blank line and "NAME." heading at every speaker change
+/// - Optionally, line numbers or citations in a margin before the lines
+pub struct TextStage<W: Write> {
+    out: W,
+    labels: Option<LineLabels>,
+}
+
+impl<W: Write> TextStage<W> {
+    pub fn new(out: W) -> TextStage<W> {
+        TextStage { out, labels: None }
+    }
+
+    /// Labels the lines in a margin, if the numbering labels any
+    pub fn with_numbering(mut self, numbering: Numbering) -> TextStage<W> {
+        self.labels = if numbering.is_on() { Some(LineLabels::new(numbering)) } else { None };
+        self
+    }
+}
+
+impl TextStage<std::io::Stdout> {
+    pub fn stdout() -> TextStage<std::io::Stdout> {
+        TextStage::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> Stage for TextStage<W> {
+    fn perform(&mut self, event: ScriptEvent) {
+        if let Some(ref mut labels) = self.labels {
+            labels.observe(&event);
+        }
+        let result = match event {
+            ScriptEvent::Fragment(index, title) => {
+                if title.trim().is_empty() {
+                    Ok(())
+                } else if index == 0 {
+                    writeln!(self.out, "{}\n", title)
+                } else {
+                    writeln!(self.out, "\n{}\n", title)
+                }
+            }
+            ScriptEvent::Enter(name) => writeln!(self.out, "[Enter {}.]", name),
+            ScriptEvent::Exit(name) => writeln!(self.out, "[Exit {}.]", name),
+            ScriptEvent::Speaker(name) => writeln!(self.out, "\n{}.", name),
+            ScriptEvent::Line(line_number, text) => match self.labels {
+                Some(ref labels) => writeln!(self.out, "{}{}", labels.margin(line_number), text),
+                None => writeln!(self.out, "{}", text),
+            },
+            ScriptEvent::EndOfDialogue => writeln!(self.out),
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) => Ok(()),
+        };
+        result.expect("Failed to write recitation");
+    }
+}
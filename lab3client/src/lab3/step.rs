@@ -0,0 +1,121 @@
+/// step.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Implements step-through mode, in which the recitation waits for a keypress after every line so a
+/// director can walk through the blocking with the program following along. Keys are read straight from the
+/// terminal, one at a time, without waiting for Enter.
+use std::io::Write;
+use super::stage::{Stage, ScriptEvent, Step};
+use super::terminal::{RawTerminal, Key};
+
+pub const STEP_HELP: &str = "Step mode: Enter or space for the next line, b to go back a line, s to skip to the \
+                             next speaker, ] to skip to the next scene, q to stop";
+
+/// Wraps another stage, choosing how to go on after each line from the key pressed:
+/// - Enter, space, j or the down or right arrow: the next line
+/// - b, k, backspace or the up or left arrow: back a line
+/// - s or tab: on to the next speaker's first line
+/// - ] or page down: on to the next scene
+/// - q or ctrl-c: stop; the rest of the recitation is dropped
+pub struct StepStage<S: Stage> {
+    inner: S,
+    terminal: Option<RawTerminal>,  // None once stopped, or once the terminal could not be read
+    stopped: bool,
+}
+
+impl<S: Stage> StepStage<S> {
+    pub fn open(inner: S) -> Result<StepStage<S>, u8> {
+        let terminal = RawTerminal::open()?;
+        writeln!(std::io::stderr().lock(), "{}", STEP_HELP).expect("Failed to write to stderr");
+        Ok(StepStage { inner, terminal: Some(terminal), stopped: false })
+    }
+
+    fn stop(&mut self) {
+        self.terminal = None;       // restores the terminal
+        self.stopped = true;
+    }
+}
+
+/// How to go on after a key, or None for keys that do nothing; stopping is left to the caller (see is_stop_key)
+pub fn step_for(key: Key) -> Option<Step> {
+    match key {
+        Key::Enter | Key::Char(' ') | Key::Char('j') | Key::Down | Key::Right => Some(Step::Continue),
+        Key::Char('b') | Key::Char('k') | Key::Backspace | Key::Up | Key::Left => Some(Step::Back),
+        Key::Char('s') | Key::Char('\t') => Some(Step::NextSpeaker),
+        Key::Char(']') | Key::PageDown => Some(Step::NextScene),
+        _ => None,
+    }
+}
+
+pub fn is_stop_key(key: Key) -> bool {
+    matches!(key, Key::Char('q') | Key::Interrupt)
+}
+
+impl<S: Stage> Stage for StepStage<S> {
+    fn perform(&mut self, event: ScriptEvent) {
+        if !self.stopped {
+            self.inner.perform(event);
+        }
+    }
+
+    fn step(&mut self) -> Step {
+        if self.stopped {
+            return Step::NextScene;
+        }
+        loop {
+            // without a terminal to read, the rest is recited without stopping
+            let key = match self.terminal.as_mut().map(|terminal| terminal.read_key()) {
+                Some(Some(key)) => key,
+                Some(None) => {
+                    writeln!(std::io::stderr().lock(),
+                             "Warning: Could not read the terminal, so the rest is recited without stepping")
+                        .expect("Failed to write to stderr");
+                    self.terminal = None;
+                    return Step::Continue;
+                }
+                None => return Step::Continue,
+            };
+            if is_stop_key(key) {
+                self.stop();
+                return Step::NextScene;
+            }
+            if let Some(step) = step_for(key) {
+                return step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_choose_how_to_go_on() {
+        for key in [Key::Enter, Key::Char(' '), Key::Char('j'), Key::Down, Key::Right] {
+            assert_eq!(step_for(key), Some(Step::Continue));
+        }
+        for key in [Key::Char('b'), Key::Char('k'), Key::Backspace, Key::Up, Key::Left] {
+            assert_eq!(step_for(key), Some(Step::Back));
+        }
+        assert_eq!(step_for(Key::Char('s')), Some(Step::NextSpeaker));
+        assert_eq!(step_for(Key::Char('\t')), Some(Step::NextSpeaker));
+        assert_eq!(step_for(Key::Char(']')), Some(Step::NextScene));
+        assert_eq!(step_for(Key::PageDown), Some(Step::NextScene));
+        for key in [Key::Char('x'), Key::Escape, Key::Home, Key::PageUp, Key::Char('q')] {
+            assert_eq!(step_for(key), None);
+        }
+        assert!(is_stop_key(Key::Char('q')) && is_stop_key(Key::Interrupt) && !is_stop_key(Key::Escape));
+    }
+
+    #[test]
+    fn stopping_drops_the_rest() {
+        let mut stage = StepStage { inner: Vec::new(), terminal: None, stopped: false };
+        stage.perform(ScriptEvent::Line(1, "Who's there?".to_string()));
+        // with no terminal to read, lines follow one another
+        assert_eq!(stage.step(), Step::Continue);
+        stage.stop();
+        assert_eq!(stage.step(), Step::NextScene);
+        stage.perform(ScriptEvent::Line(2, "Nay, answer me.".to_string()));
+        assert_eq!(stage.inner, vec![ScriptEvent::Line(1, "Who's there?".to_string())]);
+    }
+}
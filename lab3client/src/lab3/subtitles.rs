@@ -0,0 +1,204 @@
+/// subtitles.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Renders a recitation as a first-draft subtitle track in SubRip (SRT) or WebVTT. Every spoken line
+/// becomes a cue labeled with its speaker, timed the way PacedStage paces a performance: each line lasts as
+/// long as its words take at the configured words per minute, and the clock also advances at speaker changes,
+/// scene changes, stage directions and annotated pauses. The WebVTT chapters track has one cue per titled
+/// fragment, for players that show chapter markers.
+use std::io::Write;
+use super::stage::ScriptEvent;
+use super::pace::PaceSettings;
+use super::html::line_anchor;
+
+const WEBVTT_HEADER: &str = "WEBVTT";
+const SECONDS_PER_HOUR: u64 = 3600;
+const MILLISECONDS_PER_SECOND: f64 = 1000.0;
+
+/// One spoken line and when it is on screen, in seconds from the start
+struct Cue {
+    start: f64,
+    end: f64,
+    speaker: String,
+    text: String,
+    fragment_index: usize,
+    line_number: usize,
+}
+
+/// A titled fragment and the time it runs until the next one
+struct Chapter {
+    start: f64,
+    end: f64,
+    title: String,
+}
+
+/// Walks the recitation with a clock, collecting a cue for every line and a chapter for every titled fragment
+fn time_events(events: &[ScriptEvent], pace: &PaceSettings) -> (Vec<Cue>, Vec<Chapter>) {
+    let mut cues: Vec<Cue> = Vec::new();
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut clock: f64 = 0.0;
+    let mut speaker = String::new();
+    let mut fragment_index: usize = 0;
+
+    for event in events {
+        let seconds = match event {
+            ScriptEvent::Fragment(index, title) => {
+                fragment_index = *index;
+                let pause = if *index > 0 { pace.scene_pause } else { 0.0 };
+                if !title.trim().is_empty() {
+                    if let Some(previous) = chapters.last_mut() {
+                        previous.end = clock;
+                    }
+                    chapters.push(Chapter { start: clock + pause / pace.speed, end: clock, title: title.clone() });
+                }
+                pause
+            }
+            ScriptEvent::Enter(_) | ScriptEvent::Exit(_) => pace.direction_pause,
+            ScriptEvent::Speaker(name) => {
+                speaker = name.clone();
+                pace.speaker_pause
+            }
+            ScriptEvent::Line(line_number, text) => {
+                let duration = pace.speaking_time(text) / pace.speed;
+                cues.push(Cue {
+                    start: clock,
+                    end: clock + duration,
+                    speaker: speaker.clone(),
+                    text: text.clone(),
+                    fragment_index,
+                    line_number: *line_number,
+                });
+                pace.speaking_time(text)
+            }
+            ScriptEvent::Pause(seconds) => *seconds,
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::EndOfDialogue => 0.0,
+        };
+        clock += seconds / pace.speed;
+    }
+    if let Some(last) = chapters.last_mut() {
+        last.end = clock;
+    }
+    (cues, chapters)
+}
+
+/// Escapes the characters WebVTT cue text gives special meanings (which also keeps "-->" out of cues)
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Formats seconds as hours:minutes:seconds with milliseconds after the given separator
+fn timestamp(seconds: f64, separator: char) -> String {
+    let milliseconds = (seconds * MILLISECONDS_PER_SECOND).round() as u64;
+    let whole = milliseconds / 1000;
+    format!("{:02}:{:02}:{:02}{}{:03}", whole / SECONDS_PER_HOUR, whole / 60 % 60, whole % 60, separator,
+            milliseconds % 1000)
+}
+
+/// Writes SubRip cues, numbered from 1, with the speaker's name before each line
+pub fn write_srt(events: &[ScriptEvent], pace: &PaceSettings, out: &mut dyn Write) -> std::io::Result<()> {
+    let (cues, _) = time_events(events, pace);
+    for (number, cue) in cues.iter().enumerate() {
+        writeln!(out, "{}", number + 1)?;
+        writeln!(out, "{} --> {}", timestamp(cue.start, ','), timestamp(cue.end, ','))?;
+        writeln!(out, "{}: {}", cue.speaker, cue.text)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes WebVTT captions, naming each speaker with a voice span. Cue ids match the HTML line anchors.
+pub fn write_vtt(events: &[ScriptEvent], pace: &PaceSettings, out: &mut dyn Write) -> std::io::Result<()> {
+    let (cues, _) = time_events(events, pace);
+    writeln!(out, "{}", WEBVTT_HEADER)?;
+    writeln!(out)?;
+    for cue in &cues {
+        writeln!(out, "{}", line_anchor(cue.fragment_index, cue.line_number))?;
+        writeln!(out, "{} --> {}", timestamp(cue.start, '.'), timestamp(cue.end, '.'))?;
+        writeln!(out, "<v {}>{}", escape_vtt(&cue.speaker), escape_vtt(&cue.text))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes a WebVTT chapters track with a cue per titled fragment
+pub fn write_vtt_chapters(events: &[ScriptEvent], pace: &PaceSettings, out: &mut dyn Write) -> std::io::Result<()> {
+    let (_, chapters) = time_events(events, pace);
+    writeln!(out, "{}", WEBVTT_HEADER)?;
+    writeln!(out)?;
+    for (number, chapter) in chapters.iter().enumerate() {
+        writeln!(out, "chapter-{}", number + 1)?;
+        writeln!(out, "{} --> {}", timestamp(chapter.start, '.'), timestamp(chapter.end, '.'))?;
+        writeln!(out, "{}", escape_vtt(&chapter.title))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one word a second, so every duration below is easy to add up
+    fn pace(speed: f64) -> PaceSettings {
+        PaceSettings { words_per_minute: 60.0, speaker_pause: 1.0, scene_pause: 2.0, direction_pause: 0.5, speed,
+                       estimate_only: false }
+    }
+
+    fn events() -> Vec<ScriptEvent> {
+        vec![
+            ScriptEvent::Fragment(0, "Act I".to_string()),
+            ScriptEvent::Enter("Hamlet".to_string()),
+            ScriptEvent::Speaker("Hamlet".to_string()),
+            ScriptEvent::Line(1, "one two three".to_string()),
+            ScriptEvent::Pause(2.0),
+            ScriptEvent::Line(2, "<four>".to_string()),
+            ScriptEvent::Exit("Hamlet".to_string()),
+            ScriptEvent::Fragment(1, "Act II".to_string()),
+            ScriptEvent::Enter("Ophelia".to_string()),
+            ScriptEvent::Speaker("Ophelia".to_string()),
+            ScriptEvent::Line(3, "five six".to_string()),
+            ScriptEvent::EndOfDialogue,
+        ]
+    }
+
+    fn written(write: fn(&[ScriptEvent], &PaceSettings, &mut dyn Write) -> std::io::Result<()>, speed: f64) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        write(&events(), &pace(speed), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn timestamps_use_the_format_separator() {
+        assert_eq!(timestamp(0.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(1.5, ','), "00:00:01,500");
+        assert_eq!(timestamp(1.5, '.'), "00:00:01.500");
+        assert_eq!(timestamp(59.9996, '.'), "00:01:00.000");
+    }
+
+    #[test]
+    fn timestamps_roll_over_past_an_hour() {
+        assert_eq!(timestamp(3599.999, ','), "00:59:59,999");
+        assert_eq!(timestamp(3600.0, ','), "01:00:00,000");
+        assert_eq!(timestamp(3661.25, '.'), "01:01:01.250");
+        assert_eq!(timestamp(36000.0 + 59.0 * 60.0, '.'), "10:59:00.000");
+    }
+
+    #[test]
+    fn cues_are_timed_by_words_and_pauses() {
+        // enter 0.5s and speaker 1s before line 1 (3 words), then an annotated 2s pause before line 2 (1 word);
+        // the exit, the 2s scene change, an enter and a speaker change come before line 3 (2 words)
+        assert_eq!(written(write_srt, 1.0), "1\n00:00:01,500 --> 00:00:04,500\nHamlet: one two three\n\n\
+                                             2\n00:00:06,500 --> 00:00:07,500\nHamlet: <four>\n\n\
+                                             3\n00:00:11,500 --> 00:00:13,500\nOphelia: five six\n\n");
+        assert_eq!(written(write_vtt, 2.0), "WEBVTT\n\n\
+                                             fragment-0-line-1\n00:00:00.750 --> 00:00:02.250\n<v Hamlet>one two three\n\n\
+                                             fragment-0-line-2\n00:00:03.250 --> 00:00:03.750\n<v Hamlet>&lt;four&gt;\n\n\
+                                             fragment-1-line-3\n00:00:05.750 --> 00:00:06.750\n<v Ophelia>five six\n\n");
+    }
+
+    #[test]
+    fn chapters_run_from_title_to_title() {
+        assert_eq!(written(write_vtt_chapters, 1.0), "WEBVTT\n\n\
+                                                      chapter-1\n00:00:00.000 --> 00:00:08.000\nAct I\n\n\
+                                                      chapter-2\n00:00:10.000 --> 00:00:13.500\nAct II\n\n");
+    }
+}
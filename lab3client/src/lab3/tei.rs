@@ -0,0 +1,193 @@
+/// tei.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Imports TEI XML editions. Every innermost <div> of the <body> (usually a scene) becomes a fragment
+/// titled by the <head>s of it and its enclosing divs, and every <sp> adds its verse lines (<l>) and prose
+/// (<p> or <ab>, split at <lb/>) to its speaker's part. Speakers are named from the cast list through the
+/// sp's who ids, or else from its <speaker>. Stage directions and notes are skipped, since entrances and exits
+/// are worked out from the parts.
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use super::xml::{parse_xml, XmlElement, XmlNode};
+use super::play::Play;
+use super::scene_fragment::SceneFragment;
+use super::import::SceneDraft;
+use super::declarations::WHINGE_MODE;
+
+const SKIPPED_IN_LINES: [&str; 3] = ["note", "stage", "speaker"];
+const ID_ATTRIBUTES: [&str; 2] = ["xml:id", "id"];
+const ID_REFERENCE: char = '#';
+
+type CastNames = HashMap<String, String>;   // role id -> character name
+
+fn is_div(element: &XmlElement) -> bool {
+    element.name.starts_with("div")
+}
+
+/// Title of a div: its <head>, or else its type and number, such as "Act 3"
+fn div_heading(div: &XmlElement) -> String {
+    if let Some(head) = div.elements().find(|e| e.name == "head") {
+        let text = head.text_without(&["note"]);
+        if !text.is_empty() {
+            return text;
+        }
+    }
+    match (div.attribute("type"), div.attribute("n")) {
+        (Some(kind), Some(n)) => {
+            let mut kind_chars = kind.chars();
+            let kind: String = match kind_chars.next() {
+                Some(first) => first.to_uppercase().chain(kind_chars).collect(),
+                None => String::new(),
+            };
+            format!("{} {}", kind, n)
+        }
+        _ => String::new(),
+    }
+}
+
+fn cast_names(root: &XmlElement, names: &mut CastNames) {
+    for element in root.elements() {
+        if element.name == "role" {
+            if let Some(id) = ID_ATTRIBUTES.iter().find_map(|key| element.attribute(key)) {
+                let name = element.text_without(&["roleDesc", "note"]);
+                if !name.is_empty() {
+                    names.insert(id.to_string(), name);
+                }
+            }
+        }
+        cast_names(element, names);
+    }
+}
+
+fn speaker_name(sp: &XmlElement, cast: &CastNames) -> Option<String> {
+    if let Some(who) = sp.attribute("who") {
+        let names: Vec<String> = who.split_whitespace()
+            .map(|id| id.trim_start_matches(ID_REFERENCE))
+            .map(|id| cast.get(id).cloned().unwrap_or_else(|| id.to_string()))
+            .collect();
+        if !names.is_empty() {
+            return Some(names.join(" and "));
+        }
+    }
+    sp.elements().find(|e| e.name == "speaker")
+        .map(|speaker| speaker.text().trim_end_matches('.').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Splits prose at <lb/> elements into lines
+fn prose_lines(element: &XmlElement, lines: &mut Vec<String>, current: &mut String) {
+    for child in &element.children {
+        match child {
+            XmlNode::Text(text) => current.push_str(text),
+            XmlNode::Element(e) if e.name == "lb" => {
+                lines.push(std::mem::take(current));
+            }
+            XmlNode::Element(e) if SKIPPED_IN_LINES.contains(&e.name.as_str()) => {}
+            XmlNode::Element(e) => prose_lines(e, lines, current),
+        }
+    }
+}
+
+/// Adds the lines of a speech, or of a line group inside it, to the speaker's part
+fn add_speech(element: &XmlElement, speaker: &str, scene: &mut SceneDraft) {
+    for child in element.elements() {
+        match child.name.as_str() {
+            "l" => {
+                let text = child.text_without(&SKIPPED_IN_LINES);
+                if !text.is_empty() {
+                    let number = child.attribute("n").and_then(|n| n.parse::<usize>().ok());
+                    scene.add_line(speaker, number, &text);
+                }
+            }
+            "lg" => add_speech(child, speaker, scene),
+            "p" | "ab" => {
+                let mut lines: Vec<String> = Vec::new();
+                let mut current = String::new();
+                prose_lines(child, &mut lines, &mut current);
+                lines.push(current);
+                for line in lines {
+                    let text = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+                    if !text.is_empty() {
+                        scene.add_line(speaker, None, &text);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+struct TeiReader<'a> {
+    cast: &'a CastNames,
+    default_title: &'a str,
+    fragments: Vec<SceneFragment>,
+    scene: SceneDraft,
+    scene_count: usize,
+}
+
+impl TeiReader<'_> {
+    /// Walks the text in document order: innermost divs start a scene, speeches fill it
+    fn walk(&mut self, element: &XmlElement, headings: &[String]) {
+        for child in element.elements() {
+            if is_div(child) {
+                let mut headings = headings.to_vec();
+                let heading = div_heading(child);
+                if !heading.is_empty() {
+                    headings.push(heading);
+                }
+                if !child.elements().any(is_div) {
+                    self.scene_count += 1;
+                    let title = if headings.is_empty() {
+                        format!("Scene {}", self.scene_count)
+                    } else {
+                        headings.join(" ")
+                    };
+                    let previous = std::mem::replace(&mut self.scene, SceneDraft::new(title));
+                    previous.finish(&mut self.fragments, self.default_title);
+                }
+                self.walk(child, &headings);
+            } else if child.name == "sp" {
+                match speaker_name(child, self.cast) {
+                    Some(name) => add_speech(child, &name, &mut self.scene),
+                    None => {
+                        if WHINGE_MODE.load(Ordering::SeqCst) {
+                            writeln!(std::io::stderr().lock(), "Warning: Skipping a speech with no speaker")
+                                .expect("Failed to write to stderr");
+                        }
+                    }
+                }
+            } else {
+                self.walk(child, headings);
+            }
+        }
+    }
+}
+
+/// Parses a TEI document into a play. Speeches outside any div are titled after the
+/// document's title, or after default_title when it has none.
+pub fn parse_tei(text: &str, default_title: &str) -> Result<Play, String> {
+    let root = parse_xml(text)?;
+    let body = root.find("body").ok_or("no <body> element")?;
+    let title = root.find("teiHeader").and_then(|header| header.find("title")).map(|title| title.text())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| default_title.to_string());
+
+    let mut cast: CastNames = HashMap::new();
+    cast_names(&root, &mut cast);
+
+    let mut reader = TeiReader {
+        cast: &cast,
+        default_title: &title,
+        fragments: Vec::new(),
+        scene: SceneDraft::new(String::new()),
+        scene_count: 0,
+    };
+    reader.walk(body, &[]);
+    let TeiReader { mut fragments, scene, .. } = reader;
+    scene.finish(&mut fragments, &title);
+
+    if fragments.is_empty() {
+        return Err("no speeches found".to_string());
+    }
+    Ok(Play::from_fragments(fragments))
+}
@@ -0,0 +1,410 @@
+/// terminal.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Renders a recitation for a terminal. Every character gets a color of their own, chosen from their
+/// name so it is the same in every scene and every run; speaker headings are bold and stage directions dim.
+/// Lines are word wrapped at a given column or the terminal's width, measuring the columns each character
+/// really takes (wide CJK characters take two, combining marks none). When stdout is not a terminal the
+/// escapes are left out, so piped output is the plain text format. RawTerminal reads single keypresses for
+/// the interactive modes.
+use std::fs::{File, OpenOptions};
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use super::stage::{Stage, ScriptEvent};
+use super::bundle::crc32;
+use super::numbering::{Numbering, LineLabels};
+use super::declarations::FAILED_TO_OPEN_FILE;
+
+pub const COLUMNS_VARIABLE: &str = "COLUMNS";
+pub const LINES_VARIABLE: &str = "LINES";
+pub const NO_COLOR_VARIABLE: &str = "NO_COLOR";    // set to anything to turn colors off (no-color.org)
+pub const TERMINAL_DEVICE: &str = "/dev/tty";
+const WRAP_INDENT: &str = "  ";                     // continuation lines of a wrapped line
+
+pub const BOLD: &str = "\x1b[1m";
+pub const DIM: &str = "\x1b[2m";
+pub const REVERSE: &str = "\x1b[7m";
+pub const RESET: &str = "\x1b[0m";
+const ESCAPE: u8 = 0x1b;
+const INTERRUPT: u8 = 0x03;     // ctrl-c
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+const ESCAPE_TIMEOUT: &str = "1";   // tenths of a second to wait for the rest of an escape sequence
+// red, green, yellow, blue, magenta and cyan, then their bright versions
+const CHARACTER_COLORS: [&str; 12] = ["\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m",
+                                      "\x1b[91m", "\x1b[92m", "\x1b[93m", "\x1b[94m", "\x1b[95m", "\x1b[96m"];
+
+/// (start, end) code points, inclusive, of characters that take two columns
+const WIDE_RANGES: [(u32, u32); 14] = [
+    (0x1100, 0x115F), (0x2E80, 0x303E), (0x3041, 0x33FF), (0x3400, 0x4DBF), (0x4E00, 0x9FFF), (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3), (0xF900, 0xFAFF), (0xFE30, 0xFE4F), (0xFF00, 0xFF60), (0xFFE0, 0xFFE6), (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF), (0x20000, 0x3FFFD),
+];
+
+/// (start, end) code points, inclusive, of combining marks and other characters that take no columns
+const ZERO_WIDTH_RANGES: [(u32, u32); 9] = [
+    (0x0300, 0x036F), (0x0483, 0x0489), (0x0591, 0x05BD), (0x1AB0, 0x1AFF), (0x1DC0, 0x1DFF), (0x200B, 0x200F),
+    (0x20D0, 0x20FF), (0xFE00, 0xFE0F), (0xFE20, 0xFE2F),
+];
+
+/// Columns a character takes in a terminal
+pub fn char_width(c: char) -> usize {
+    let code = c as u32;
+    let within = |ranges: &[(u32, u32)]| ranges.iter().any(|(start, end)| (*start..=*end).contains(&code));
+    if c.is_control() || within(&ZERO_WIDTH_RANGES) {
+        0
+    } else if within(&WIDE_RANGES) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Columns a string takes in a terminal
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Breaks text into lines of at most width columns at spaces; continuation lines start with WRAP_INDENT.
+/// A word too long for a line of its own is left whole.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = display_width(word);
+        if !line.is_empty() && line_width + 1 + word_width > width {
+            lines.push(std::mem::replace(&mut line, WRAP_INDENT.to_string()));
+            line_width = display_width(WRAP_INDENT);
+        } else if !line.is_empty() {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The terminal's (rows, columns): from the COLUMNS and LINES variables if set, or else from stty
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let from_stty = || File::open(TERMINAL_DEVICE).ok()
+        .and_then(|tty| Command::new("stty").arg("size").stdin(tty).stderr(Stdio::null()).output().ok())
+        .and_then(|output| {
+            let size = String::from_utf8_lossy(&output.stdout).to_string();
+            let mut numbers = size.split_whitespace().map(|n| n.parse::<usize>());
+            match (numbers.next(), numbers.next()) {
+                (Some(Ok(rows)), Some(Ok(columns))) if rows > 0 && columns > 0 => Some((rows, columns)),
+                _ => None,
+            }
+        });
+    let variable = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<usize>().ok()).filter(|n| *n > 0);
+    match (variable(LINES_VARIABLE), variable(COLUMNS_VARIABLE)) {
+        (Some(rows), Some(columns)) => Some((rows, columns)),
+        (rows, columns) => from_stty().map(|(tty_rows, tty_columns)| (rows.unwrap_or(tty_rows),
+                                                                       columns.unwrap_or(tty_columns))),
+    }
+}
+
+/// The escape sequence that colors a character's name and lines
+pub fn character_color(name: &str) -> &'static str {
+    CHARACTER_COLORS[crc32(name.as_bytes()) as usize % CHARACTER_COLORS.len()]
+}
+
+/// Writes the recitation in the plain text layout, colored and wrapped for a terminal
+pub struct TerminalStage<W: Write> {
+    out: W,
+    color: bool,
+    width: Option<usize>,       // wrap column, or None to leave lines unwrapped
+    speaker: String,
+    labels: Option<LineLabels>,
+}
+
+impl<W: Write> TerminalStage<W> {
+    pub fn new(out: W, color: bool, width: Option<usize>) -> TerminalStage<W> {
+        TerminalStage { out, color, width, speaker: String::new(), labels: None }
+    }
+
+    /// Labels the lines in a dim margin, if the numbering labels any
+    pub fn with_numbering(mut self, numbering: Numbering) -> TerminalStage<W> {
+        self.labels = if numbering.is_on() { Some(LineLabels::new(numbering)) } else { None };
+        self
+    }
+
+    fn write_styled(&mut self, style: &str, text: &str) -> std::io::Result<()> {
+        self.write_in_margin("", style, text)
+    }
+
+    /// Writes text after a margin, wrapped to the width left beside it, between the given escape and a reset
+    /// when coloring. Wrapped lines get a blank margin.
+    fn write_in_margin(&mut self, margin: &str, style: &str, text: &str) -> std::io::Result<()> {
+        let margin_width = display_width(margin);
+        let lines = match self.width {
+            Some(width) => wrap(text, width.saturating_sub(margin_width).max(1)),
+            None => vec![text.to_string()],
+        };
+        for (i, line) in lines.into_iter().enumerate() {
+            let margin = if i == 0 { margin.to_string() } else { " ".repeat(margin_width) };
+            if self.color && !margin.trim().is_empty() {
+                write!(self.out, "{}{}{}", DIM, margin, RESET)?;
+            } else {
+                write!(self.out, "{}", margin)?;
+            }
+            if self.color && !style.is_empty() {
+                writeln!(self.out, "{}{}{}", style, line, RESET)?;
+            } else {
+                writeln!(self.out, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TerminalStage<std::io::Stdout> {
+    /// A stage on stdout, colored only if stdout is a terminal and NO_COLOR is unset, and wrapped at the
+    /// given column or else the terminal's width
+    pub fn stdout(wrap_column: Option<usize>) -> TerminalStage<std::io::Stdout> {
+        let is_terminal = std::io::stdout().is_terminal();
+        let color = is_terminal && std::env::var_os(NO_COLOR_VARIABLE).is_none();
+        let width = match wrap_column {
+            Some(column) => Some(column),
+            None if is_terminal => terminal_size().map(|(_, columns)| columns),
+            None => None,
+        };
+        TerminalStage::new(std::io::stdout(), color, width)
+    }
+}
+
+impl<W: Write> Stage for TerminalStage<W> {
+    fn perform(&mut self, event: ScriptEvent) {
+        if let Some(ref mut labels) = self.labels {
+            labels.observe(&event);
+        }
+        let result = match event {
+            ScriptEvent::Fragment(index, title) => {
+                if title.trim().is_empty() {
+                    Ok(())
+                } else {
+                    let leading = if index == 0 { Ok(()) } else { writeln!(self.out) };
+                    leading.and_then(|_| self.write_styled(BOLD, &title)).and_then(|_| writeln!(self.out))
+                }
+            }
+            ScriptEvent::Enter(name) => self.write_styled(DIM, &format!("[Enter {}.]", name)),
+            ScriptEvent::Exit(name) => self.write_styled(DIM, &format!("[Exit {}.]", name)),
+            ScriptEvent::Speaker(name) => {
+                let style = format!("{}{}", BOLD, character_color(&name));
+                self.speaker = name;
+                writeln!(self.out).and_then(|_| self.write_styled(&style, &format!("{}.", self.speaker)))
+            }
+            ScriptEvent::Line(line_number, text) => {
+                let style = character_color(&self.speaker);
+                let margin = self.labels.as_ref().map(|labels| labels.margin(line_number)).unwrap_or_default();
+                self.write_in_margin(&margin, style, &text)
+            }
+            ScriptEvent::EndOfDialogue => writeln!(self.out),
+            ScriptEvent::Citation(_, _, _) | ScriptEvent::Pause(_) => Ok(()),
+        };
+        result.expect("Failed to write recitation");
+    }
+}
+
+/// A keypress read from the terminal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Escape,
+    Interrupt,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+}
+
+/// Runs stty on the terminal, returning what it prints
+fn stty(args: &[&str]) -> std::io::Result<String> {
+    let tty = File::open(TERMINAL_DEVICE)?;
+    let output = Command::new("stty").args(args).stdin(tty).stderr(Stdio::null()).output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("stty {} failed", args.join(" "))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The controlling terminal, reading a key at a time with no echo and with ctrl-c delivered as a key, so the
+/// caller can always restore it. Output processing is left on. The previous settings come back when dropped.
+pub struct RawTerminal {
+    tty: File,
+    saved: String,      // stty -g settings to restore
+    pending: Option<u8>,    // a byte read after a lone escape, to be read again as a key of its own
+}
+
+impl RawTerminal {
+    pub fn open() -> Result<RawTerminal, u8> {
+        let result = OpenOptions::new().read(true).write(true).open(TERMINAL_DEVICE).and_then(|tty| {
+            let saved = stty(&["-g"])?;
+            stty(&["-icanon", "-echo", "-isig", "min", "1", "time", "0"])?;
+            Ok(RawTerminal { tty, saved, pending: None })
+        });
+        result.map_err(|e| {
+            writeln!(std::io::stderr().lock(), "Error: Failed to set up the terminal '{}': {}", TERMINAL_DEVICE, e)
+                .expect("Failed to write to stderr");
+            FAILED_TO_OPEN_FILE
+        })
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        if let Some(byte) = self.pending.take() {
+            return Some(byte);
+        }
+        let mut byte = [0u8; 1];
+        match self.tty.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+
+    /// Waits for a keypress, or returns None when the terminal can no longer be read
+    pub fn read_key(&mut self) -> Option<Key> {
+        let byte = self.read_byte()?;
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            BACKSPACE | DELETE => Key::Backspace,
+            INTERRUPT => Key::Interrupt,
+            ESCAPE => self.read_escape_sequence()?,
+            _ if byte < 0x80 => Key::Char(byte as char),
+            _ => {
+                // the rest of a UTF-8 sequence: one continuation byte per leading one bit after the first
+                let mut bytes = vec![byte];
+                for _ in 1..byte.leading_ones() {
+                    bytes.push(self.read_byte()?);
+                }
+                Key::Char(String::from_utf8_lossy(&bytes).chars().next().unwrap_or(char::REPLACEMENT_CHARACTER))
+            }
+        };
+        Some(key)
+    }
+
+    /// Reads the rest of an escape sequence for a cursor or paging key. The terminal sends a sequence all at
+    /// once, so reads time out briefly meanwhile: an escape with nothing right after it is a lone escape, and a
+    /// byte that cannot start a sequence is kept to be read as the next key.
+    fn read_escape_sequence(&mut self) -> Option<Key> {
+        if stty(&["min", "0", "time", ESCAPE_TIMEOUT]).is_err() {
+            return None;
+        }
+        let key = self.read_escape_tail();
+        stty(&["min", "1", "time", "0"]).ok()?;
+        Some(key)
+    }
+
+    fn read_escape_tail(&mut self) -> Key {
+        let introducer = match self.read_byte() {
+            Some(introducer) => introducer,
+            None => return Key::Escape,
+        };
+        if introducer != b'[' && introducer != b'O' {
+            self.pending = Some(introducer);
+            return Key::Escape;
+        }
+        let mut parameter = String::new();
+        loop {
+            let byte = match self.read_byte() {
+                Some(byte) => byte,
+                None => return Key::Escape,
+            };
+            match byte {
+                b'0'..=b'9' | b';' => parameter.push(byte as char),
+                b'A' => return Key::Up,
+                b'B' => return Key::Down,
+                b'C' => return Key::Right,
+                b'D' => return Key::Left,
+                b'H' => return Key::Home,
+                b'F' => return Key::End,
+                b'~' => {
+                    return match parameter.as_str() {
+                        "1" | "7" => Key::Home,
+                        "4" | "8" => Key::End,
+                        "5" => Key::PageUp,
+                        "6" => Key::PageDown,
+                        _ => Key::Escape,
+                    }
+                }
+                _ => return Key::Escape,
+            }
+        }
+    }
+}
+
+impl Write for RawTerminal {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.tty.write(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.tty.flush()
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::play::Play;
+    use super::super::stage::TextStage;
+
+    const NESTED_BUNDLE: &str = "test/test_15/nested.play";
+
+    #[test]
+    fn widths_count_terminal_columns() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('漢'), 2);
+        assert_eq!(char_width('\u{301}'), 0);   // combining acute accent
+        assert_eq!(char_width('\t'), 0);
+        assert_eq!(display_width("Ophelia"), 7);
+        assert_eq!(display_width("ハムレット"), 10);
+        assert_eq!(display_width("Ame\u{301}lie"), 6);
+    }
+
+    #[test]
+    fn wrapping_indents_continuations_and_keeps_long_words_whole() {
+        assert_eq!(wrap("To be, or not to be", 10), vec!["To be, or", "  not to", "  be"]);
+        assert_eq!(wrap("To be, or not to be", 80), vec!["To be, or not to be"]);
+        assert_eq!(wrap("a honorificabilitudinitatibus b", 8), vec!["a", "  honorificabilitudinitatibus", "  b"]);
+        assert_eq!(wrap("", 10), vec![""]);
+        // wide characters fill the line twice as fast, combining marks not at all
+        assert_eq!(wrap("漢字 漢字 漢字", 10), vec!["漢字 漢字", "  漢字"]);
+        assert_eq!(wrap("e\u{301}e\u{301}e\u{301} e\u{301}e\u{301}e\u{301}", 7),
+                   vec!["e\u{301}e\u{301}e\u{301} e\u{301}e\u{301}e\u{301}"]);
+    }
+
+    // players move on as they speak, so every recitation needs a play of its own
+    fn recite(stage: &mut dyn Stage) {
+        let mut play = Play::new();
+        play.prepare(&NESTED_BUNDLE.to_string()).unwrap();
+        play.recite(stage);
+    }
+
+    #[test]
+    fn uncolored_unwrapped_output_is_the_plain_format() {
+        for numbering in [Numbering::default(), Numbering { every: 5, citations: false }] {
+            let mut plain: Vec<u8> = Vec::new();
+            recite(&mut TextStage::new(&mut plain).with_numbering(numbering));
+            let mut terminal: Vec<u8> = Vec::new();
+            recite(&mut TerminalStage::new(&mut terminal, false, None).with_numbering(numbering));
+            assert!(!plain.is_empty());
+            assert_eq!(String::from_utf8(terminal).unwrap(), String::from_utf8(plain).unwrap());
+        }
+    }
+}
@@ -0,0 +1,84 @@
+/// transcript.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: Imports a plain transcript in the format Play::recite prints. After a blank line, a line followed
+/// by another blank line is a scene title and a "Name." line followed by text is a speaker heading whose
+/// speech runs to the next blank line. Enter and exit directives only occur between fragments, so one that
+/// follows dialogue starts a new, untitled fragment, as do two blank lines in a row (the end of a fragment's
+/// dialogue followed by the next speaker). Characters who enter but never speak get empty parts. Lines are
+/// numbered from 1 in each fragment.
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use super::play::Play;
+use super::scene_fragment::SceneFragment;
+use super::import::SceneDraft;
+use super::declarations::WHINGE_MODE;
+
+const ENTER_PREFIX: &str = "[Enter ";
+const EXIT_PREFIX: &str = "[Exit ";
+const DIRECTIVE_SUFFIX: &str = ".]";
+const FRAGMENT_BREAK_BLANK_LINES: usize = 2;
+
+fn is_directive(line: &str) -> bool {
+    (line.starts_with(ENTER_PREFIX) || line.starts_with(EXIT_PREFIX)) && line.ends_with(DIRECTIVE_SUFFIX)
+}
+
+/// Parses a transcript into a play. Dialogue before the first title is titled after default_title.
+pub fn parse_transcript(text: &str, default_title: &str) -> Result<Play, String> {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let mut fragments: Vec<SceneFragment> = Vec::new();
+    let mut scene = SceneDraft::new(String::new());
+    let mut speaker: Option<String> = None;
+    let mut blank_lines: usize = 0;
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            speaker = None;
+            blank_lines += 1;
+            continue;
+        }
+        let fragment_break = blank_lines >= FRAGMENT_BREAK_BLANK_LINES;
+        blank_lines = 0;
+
+        if (fragment_break || is_directive(line)) && scene.has_lines() {
+            let previous = std::mem::replace(&mut scene, SceneDraft::new(String::new()));
+            previous.finish(&mut fragments, default_title);
+        }
+
+        if is_directive(line) {
+            speaker = None;
+            if let Some(name) = line.strip_prefix(ENTER_PREFIX).and_then(|rest| rest.strip_suffix(DIRECTIVE_SUFFIX)) {
+                scene.add_player(name);
+            }
+        } else if let Some(ref name) = speaker {
+            scene.add_line(name, None, line.trim());
+        } else {
+            let next_blank = lines.get(i + 1).map(|next| next.trim().is_empty()).unwrap_or(true);
+            let next_directive = lines.get(i + 1).map(|next| is_directive(next)).unwrap_or(false);
+            match line.trim().strip_suffix('.') {
+                Some(name) if !next_blank && !next_directive && !name.is_empty() => {
+                    speaker = Some(name.to_string());
+                }
+                _ if next_blank => {
+                    // a title names the fragment that is starting, which may already have entrances
+                    if scene.has_lines() {
+                        let previous = std::mem::replace(&mut scene, SceneDraft::new(String::new()));
+                        previous.finish(&mut fragments, default_title);
+                    }
+                    scene.set_title(line.trim());
+                }
+                _ => {
+                    if WHINGE_MODE.load(Ordering::SeqCst) {
+                        writeln!(std::io::stderr().lock(), "Warning: Skipping line {} with no speaker: '{}'", i + 1, line)
+                            .expect("Failed to write to stderr");
+                    }
+                }
+            }
+        }
+    }
+    scene.finish(&mut fragments, default_title);
+
+    if fragments.is_empty() {
+        return Err("no dialogue found".to_string());
+    }
+    Ok(Play::from_fragments(fragments))
+}
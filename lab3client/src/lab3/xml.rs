@@ -0,0 +1,327 @@
+/// xml.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A small XML reader that builds an element tree, enough for reading published editions without any
+/// external crates. Declarations, processing instructions, comments and the DOCTYPE are skipped, CDATA is kept
+/// as text and the predefined and numeric entities are decoded. Element names lose their namespace prefix.
+/// Elements nested more than 256 deep are refused, so a hostile file cannot overflow the stack.
+
+#[derive(Clone, Debug)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+impl XmlElement {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|child| match child {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        })
+    }
+
+    /// First descendant element with the given name, searching depth first
+    pub fn find(&self, name: &str) -> Option<&XmlElement> {
+        for element in self.elements() {
+            if element.name == name {
+                return Some(element);
+            }
+            if let Some(found) = element.find(name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// All text inside the element, skipping the named elements, with runs of whitespace collapsed
+    pub fn text_without(&self, skipped: &[&str]) -> String {
+        let mut text = String::new();
+        self.collect_text(skipped, &mut text);
+        text.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
+    pub fn text(&self) -> String {
+        self.text_without(&[])
+    }
+
+    fn collect_text(&self, skipped: &[&str], text: &mut String) {
+        for child in &self.children {
+            match child {
+                XmlNode::Text(t) => text.push_str(t),
+                XmlNode::Element(element) if !skipped.contains(&element.name.as_str()) => {
+                    element.collect_text(skipped, text)
+                }
+                XmlNode::Element(_) => {}
+            }
+        }
+    }
+}
+
+/// Decodes the predefined and numeric character references; unknown entities are kept as written
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let replacement = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else {
+                    entity.strip_prefix('#').and_then(|digits| digits.parse::<u32>().ok())
+                };
+                code.and_then(char::from_u32)
+            }
+        };
+        match replacement {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+const MAX_DEPTH: usize = 256;
+
+fn local_name(name: &str) -> String {
+    match name.rsplit_once(':') {
+        Some((_, local)) => local.to_string(),
+        None => name.to_string(),
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    position: usize,
+    depth: usize,   // elements open at the current position
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.position].matches('\n').count() + 1;
+        format!("{} on line {}", message, line)
+    }
+
+    /// Skips past the next occurrence of the terminator
+    fn skip_past(&mut self, terminator: &str) -> Result<(), String> {
+        match self.rest().find(terminator) {
+            Some(end) => {
+                self.position += end + terminator.len();
+                Ok(())
+            }
+            None => Err(self.error(&format!("missing '{}'", terminator))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.position = self.text.len() - trimmed.len();
+    }
+
+    /// Skips a DOCTYPE, including any internal subset in brackets
+    fn skip_doctype(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        for (offset, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '>' if depth == 0 => {
+                    self.position += offset + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(self.error("unterminated DOCTYPE"))
+    }
+
+    /// Skips everything that is not an element: whitespace, declarations, comments and DOCTYPE
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<!DOCTYPE") || rest.starts_with("<!doctype") {
+                self.skip_doctype()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let rest = self.rest();
+        let end = rest.find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=').unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.position += end;
+        Ok(rest[..end].to_string())
+    }
+
+    fn element(&mut self) -> Result<XmlElement, String> {
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected an element"));
+        }
+        self.position += 1;
+        let name = self.name()?;
+        let mut element = XmlElement { name: local_name(&name), attributes: Vec::new(), children: Vec::new() };
+
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if rest.starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error(&format!("attribute '{}' has no value", key)));
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => return Err(self.error(&format!("attribute '{}' is not quoted", key))),
+            };
+            self.position += 1;
+            let end = match self.rest().find(quote) {
+                Some(end) => end,
+                None => return Err(self.error("unterminated attribute value")),
+            };
+            let value = decode_entities(&self.rest()[..end]);
+            self.position += end + 1;
+            element.attributes.push((key, value));
+        }
+
+        // content, up to the matching end tag
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(self.error(&format!("unclosed element '{}'", name)));
+            }
+            if rest.starts_with("</") {
+                self.position += 2;
+                let end_name = self.name()?;
+                if end_name != name {
+                    return Err(self.error(&format!("'{}' closed by '{}'", name, end_name)));
+                }
+                self.skip_past(">")?;
+                return Ok(element);
+            } else if rest.starts_with("<!--") || rest.starts_with("<?") {
+                let terminator = if rest.starts_with("<!--") { "-->" } else { "?>" };
+                self.skip_past(terminator)?;
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = match cdata.find("]]>") {
+                    Some(end) => end,
+                    None => return Err(self.error("unterminated CDATA section")),
+                };
+                element.children.push(XmlNode::Text(cdata[..end].to_string()));
+                self.position += "<![CDATA[".len() + end + "]]>".len();
+            } else if rest.starts_with('<') {
+                if self.depth >= MAX_DEPTH {
+                    return Err(self.error("elements nested too deep"));
+                }
+                self.depth += 1;
+                let child = self.element()?;
+                self.depth -= 1;
+                element.children.push(XmlNode::Element(child));
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                element.children.push(XmlNode::Text(decode_entities(&rest[..end])));
+                self.position += end;
+            }
+        }
+    }
+}
+
+/// Parses an XML document, returning its root element
+pub fn parse_xml(text: &str) -> Result<XmlElement, String> {
+    let mut reader = Reader { text, position: 0, depth: 0 };
+    if reader.rest().starts_with('\u{feff}') {
+        reader.position += '\u{feff}'.len_utf8();
+    }
+    reader.skip_misc()?;
+    let root = reader.element()?;
+    reader.skip_misc()?;
+    if !reader.rest().is_empty() {
+        return Err(reader.error("unexpected content after the root element"));
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::html::escape_html;
+
+    const AWKWARD_TEXT: &str = "Tom & Jerry <aside> \"quoted\" 'apostrophe' — ✓ 🎭";
+
+    #[test]
+    fn escaped_text_round_trips() {
+        let document = format!("<?xml version=\"1.0\"?>\n<!DOCTYPE play>\n<!-- a comment -->\n\
+                                <tei:play><sp who=\"{}\">{}<![CDATA[ <kept> & ]]><stage>aside</stage></sp></tei:play>",
+                               escape_html(AWKWARD_TEXT), escape_html(AWKWARD_TEXT));
+        let root = parse_xml(&document).unwrap();
+        assert_eq!(root.name, "play");
+        let speech = root.find("sp").unwrap();
+        assert_eq!(speech.attribute("who"), Some(AWKWARD_TEXT));
+        assert_eq!(speech.text_without(&["stage"]), format!("{} <kept> &", AWKWARD_TEXT));
+        assert_eq!(speech.find("stage").unwrap().text(), "aside");
+    }
+
+    #[test]
+    fn deep_nesting_is_refused() {
+        let nested = |depth: usize| format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+        assert!(parse_xml(&nested(MAX_DEPTH + 1)).is_ok());
+        assert!(parse_xml(&nested(MAX_DEPTH + 2)).unwrap_err().starts_with("elements nested too deep"));
+        assert!(parse_xml(&"<a>".repeat(200000)).unwrap_err().starts_with("elements nested too deep"));
+    }
+
+    #[test]
+    fn entities_decode() {
+        assert_eq!(decode_entities("&lt;&#65;&#x42;&amp;&unknown;&"), "<AB&&unknown;&");
+        assert!(parse_xml("<a><b></a>").is_err());
+        assert!(parse_xml("<a/><b/>").is_err());
+    }
+}
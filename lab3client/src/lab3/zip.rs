@@ -0,0 +1,102 @@
+/// zip.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A small ZIP writer that stores its entries uncompressed, enough to build EPUB containers without
+/// any external crates.
+use std::io::Write;
+use super::bundle::crc32;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const VERSION: u16 = 20;            // 2.0, the first version with directories and stored entries
+const STORED: u16 = 0;              // compression method for uncompressed entries
+const DOS_EPOCH_YEAR: u64 = 1980;
+
+struct ZipEntry {
+    name: String,
+    contents: Vec<u8>,
+    crc: u32,
+}
+
+/// Collects entries in order, then writes the archive in one pass
+pub struct ZipWriter {
+    entries: Vec<ZipEntry>,
+    time: u16,      // MS-DOS time and date shared by every entry
+    date: u16,
+}
+
+impl ZipWriter {
+    /// A writer whose entries are dated from the given calendar date and time (UTC)
+    pub fn new(year: u64, month: u64, day: u64, hour: u64, minute: u64, second: u64) -> ZipWriter {
+        let year = year.clamp(DOS_EPOCH_YEAR, DOS_EPOCH_YEAR + 127);
+        ZipWriter {
+            entries: Vec::new(),
+            time: ((hour << 11) | (minute << 5) | (second / 2)) as u16,
+            date: (((year - DOS_EPOCH_YEAR) << 9) | (month << 5) | day) as u16,
+        }
+    }
+
+    pub fn add(&mut self, name: &str, contents: Vec<u8>) {
+        let crc = crc32(&contents);
+        self.entries.push(ZipEntry { name: name.to_string(), contents, crc });
+    }
+
+    fn write_u16(out: &mut dyn Write, value: u16) -> std::io::Result<()> {
+        out.write_all(&value.to_le_bytes())
+    }
+
+    fn write_u32(out: &mut dyn Write, value: u32) -> std::io::Result<()> {
+        out.write_all(&value.to_le_bytes())
+    }
+
+    /// The fields local and central headers share, from the version needed to the extra field length
+    fn write_common_fields(&self, entry: &ZipEntry, out: &mut dyn Write) -> std::io::Result<()> {
+        Self::write_u16(out, VERSION)?;
+        Self::write_u16(out, 0)?;                           // flags
+        Self::write_u16(out, STORED)?;
+        Self::write_u16(out, self.time)?;
+        Self::write_u16(out, self.date)?;
+        Self::write_u32(out, entry.crc)?;
+        Self::write_u32(out, entry.contents.len() as u32)?; // compressed size
+        Self::write_u32(out, entry.contents.len() as u32)?; // uncompressed size
+        Self::write_u16(out, entry.name.len() as u16)?;
+        Self::write_u16(out, 0)                             // extra field length
+    }
+
+    /// Writes the local entries, then the central directory and its end record
+    pub fn finish(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut offsets: Vec<u32> = Vec::new();
+        let mut offset: usize = 0;
+        for entry in &self.entries {
+            offsets.push(offset as u32);
+            Self::write_u32(out, LOCAL_HEADER_SIGNATURE)?;
+            self.write_common_fields(entry, out)?;
+            out.write_all(entry.name.as_bytes())?;
+            out.write_all(&entry.contents)?;
+            offset += 30 + entry.name.len() + entry.contents.len();
+        }
+
+        let directory_offset = offset;
+        for (entry, entry_offset) in self.entries.iter().zip(&offsets) {
+            Self::write_u32(out, CENTRAL_HEADER_SIGNATURE)?;
+            Self::write_u16(out, VERSION)?;                 // version made by
+            self.write_common_fields(entry, out)?;
+            Self::write_u16(out, 0)?;                       // comment length
+            Self::write_u16(out, 0)?;                       // disk number
+            Self::write_u16(out, 0)?;                       // internal attributes
+            Self::write_u32(out, 0)?;                       // external attributes
+            Self::write_u32(out, *entry_offset)?;
+            out.write_all(entry.name.as_bytes())?;
+            offset += 46 + entry.name.len();
+        }
+
+        Self::write_u32(out, END_OF_DIRECTORY_SIGNATURE)?;
+        Self::write_u16(out, 0)?;                           // this disk
+        Self::write_u16(out, 0)?;                           // disk with the directory
+        Self::write_u16(out, self.entries.len() as u16)?;
+        Self::write_u16(out, self.entries.len() as u16)?;
+        Self::write_u32(out, (offset - directory_offset) as u32)?;
+        Self::write_u32(out, directory_offset as u32)?;
+        Self::write_u16(out, 0)                             // comment length
+    }
+}
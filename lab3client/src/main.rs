@@ -1,29 +1,70 @@
 /// main.rs
 /// Author: Zichu Pan, Edgar Palomino
-/// Summary: This is the entry point for the program. It handles command-line argument 
+/// Summary: This is the entry point for the program. It handles command-line argument
 /// parsing and orchestrates the overall execution flow.
 use std::env;
 use std::sync::atomic::Ordering;
 use std::io::Write;
 
 pub mod lab3;
-use lab3::declarations::{MIN_ARGS, MAX_ARGS, PROGRAM_NAME_INDEX, CONFIG_FILE_INDEX, 
+use lab3::declarations::{MIN_ARGS, MAX_ARGS, PROGRAM_NAME_INDEX, CONFIG_FILE_INDEX,
                          VERBOSE_FLAG_INDEX, BAD_COMMAND_LINE_ERROR, SUCCESS,
-                         WHINGE_MODE};
+                         WHINGE_MODE, COMMAND_INDEX, DIRECTOR_COMMAND, ACTOR_COMMAND, BROADCAST_COMMAND,
+                         ROLE_MIN_ARGS, ROLE_MAX_ARGS, ROLE_ADDRESS_INDEX, ROLE_TARGET_INDEX,
+                         ROLE_VERBOSE_FLAG_INDEX, REHEARSE_COMMAND, SCRIPT_COMMAND_MIN_ARGS,
+                         SCRIPT_COMMAND_MAX_ARGS, SCRIPT_COMMAND_FILE_INDEX, SCRIPT_COMMAND_VERBOSE_FLAG_INDEX,
+                         REPORT_COMMAND, REPORT_MIN_ARGS, REPORT_FILES_INDEX, SIDES_COMMAND, IMPORT_COMMAND,
+                         PACK_COMMAND, UNPACK_COMMAND, BROWSE_COMMAND, COMMANDS};
 use lab3::play::Play;
+use lab3::director::Director;
+use lab3::actor::Actor;
+use lab3::broadcast::BroadcastStage;
+use lab3::stage::{Stage, TextStage};
+use lab3::pace::PacedStage;
+use lab3::terminal::TerminalStage;
+use lab3::step::StepStage;
+use lab3::pager::browse;
+use lab3::selection::select;
+use lab3::options::{RecitalOptions, AS_OPTION, OUTPUT_OPTION, STEP_OPTION, FORMAT_OPTION, ESTIMATE_OPTION,
+                    SCENE_OPTION, LINES_OPTION, ONLY_OPTION};
+use lab3::rehearsal::RehearsalStage;
+use lab3::scoring::{Scorecard, report_history};
+use lab3::sides::SidesStage;
+use lab3::export::{export, open_output, SSML_FORMAT};
+use lab3::ssml::export_ssml;
+use lab3::import::import;
+use lab3::bundle::{pack, unpack};
 use lab3::return_wrapper::ReturnWrapper;
 
 fn usage(program_name: &String) {
-    writeln!(std::io::stdout().lock(), "usage: {} <script_file_name> [whinge]", program_name)
+    writeln!(std::io::stdout().lock(), "usage: {} <script_file_name> [whinge] [options]", program_name)
         .expect("Failed to write to stdout");
-}
-
-fn parse_args(script_filename: &mut String) -> Result<(), u8> {
-    let mut args: Vec<String> = Vec::new();
-    for arg in env::args() {
-        args.push(arg);
+    writeln!(std::io::stdout().lock(), "       {} {} <network_address> <script_file_name> [whinge]",
+             program_name, DIRECTOR_COMMAND).expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} <network_address> <character_name> [whinge]",
+             program_name, ACTOR_COMMAND).expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} <network_address> <script_file_name> [whinge]",
+             program_name, BROADCAST_COMMAND).expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} {} <character_name> <script_file_name> [whinge]",
+             program_name, REHEARSE_COMMAND, AS_OPTION).expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} <history_file>...", program_name, REPORT_COMMAND)
+        .expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} [{} <character_name> | {} <directory>] <script_file_name> [whinge]",
+             program_name, SIDES_COMMAND, AS_OPTION, OUTPUT_OPTION).expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} [{} <character_name>] <script_file_name> [whinge]",
+             program_name, BROWSE_COMMAND, AS_OPTION).expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} <source_file> <output_directory> [whinge]", program_name, IMPORT_COMMAND)
+        .expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} <script_file_name> <bundle_file> [whinge]", program_name, PACK_COMMAND)
+        .expect("Failed to write to stdout");
+    writeln!(std::io::stdout().lock(), "       {} {} <bundle_file> <output_directory> [whinge]", program_name, UNPACK_COMMAND)
+        .expect("Failed to write to stdout");
+    for option_usage in RecitalOptions::usage() {
+        writeln!(std::io::stdout().lock(), "options: {}", option_usage).expect("Failed to write to stdout");
     }
+}
 
+fn parse_args(args: &[String], script_filename: &mut String) -> Result<(), u8> {
     if args.len() < MIN_ARGS || args.len() > MAX_ARGS {
         usage(&args[PROGRAM_NAME_INDEX]);
         return Err(BAD_COMMAND_LINE_ERROR);
@@ -39,24 +80,340 @@ fn parse_args(script_filename: &mut String) -> Result<(), u8> {
     if args.len() == MAX_ARGS && args[VERBOSE_FLAG_INDEX] == "whinge" {
         WHINGE_MODE.store(true, Ordering::SeqCst);
     }
-    
+
     Ok(())
 }
-    
-fn main() -> ReturnWrapper {
+
+/// Parses: program_name director|actor|broadcast <network_address> <script_file_name|character_name> [whinge]
+/// and: program_name import|pack|unpack <source> <destination> [whinge]
+fn parse_role_args(args: &[String], address: &mut String, target: &mut String) -> Result<(), u8> {
+    if args.len() < ROLE_MIN_ARGS || args.len() > ROLE_MAX_ARGS
+        || (args.len() == ROLE_MAX_ARGS && args[ROLE_VERBOSE_FLAG_INDEX] != "whinge") {
+        usage(&args[PROGRAM_NAME_INDEX]);
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+
+    *address = args[ROLE_ADDRESS_INDEX].clone();
+    *target = args[ROLE_TARGET_INDEX].clone();
+
+    if args.len() == ROLE_MAX_ARGS {
+        WHINGE_MODE.store(true, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+/// Parses: program_name <command> <script_file_name> [whinge]
+fn parse_script_command_args(args: &[String], script_filename: &mut String) -> Result<(), u8> {
+    if args.len() < SCRIPT_COMMAND_MIN_ARGS || args.len() > SCRIPT_COMMAND_MAX_ARGS
+        || (args.len() == SCRIPT_COMMAND_MAX_ARGS && args[SCRIPT_COMMAND_VERBOSE_FLAG_INDEX] != "whinge") {
+        usage(&args[PROGRAM_NAME_INDEX]);
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+
+    *script_filename = args[SCRIPT_COMMAND_FILE_INDEX].clone();
+
+    if args.len() == SCRIPT_COMMAND_MAX_ARGS {
+        WHINGE_MODE.store(true, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+/// Runs a performance on the given stage, pacing it in real time when pacing options were given.
+/// Returns the stage so that callers can finish with it.
+fn stage_performance<S: Stage>(stage: S, options: &RecitalOptions,
+                               perform: impl FnOnce(&mut dyn Stage) -> Result<(), u8>) -> Result<S, u8> {
+    let pace = match options.pace {
+        Some(ref pace) => pace.clone(),
+        None => {
+            let mut stage = stage;
+            perform(&mut stage)?;
+            return Ok(stage);
+        }
+    };
+
+    let mut paced = PacedStage::new(stage, pace.clone());
+    // while stepping, every key belongs to StepStage; a second reader of the terminal would take some of them
+    if !pace.estimate_only && !options.step {
+        paced.listen_for_speed_changes();
+    }
+    perform(&mut paced)?;
+
+    let seconds = paced.running_time().as_secs();
+    let estimate = format!("Estimated running time: {}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if pace.estimate_only {
+        writeln!(std::io::stdout().lock(), "{}", estimate).expect("Failed to write to stdout");
+    } else {
+        writeln!(std::io::stderr().lock(), "{}", estimate).expect("Failed to write to stderr");
+    }
+    Ok(paced.into_inner())
+}
+
+/// Recites the play on the given stage, stepping through it a keypress at a time if asked to
+fn recite_on<S: Stage>(play: &mut Play, stage: S, options: &RecitalOptions) -> Result<(), u8> {
+    let recite = |stage: &mut dyn Stage| {
+        play.recite(stage);
+        Ok(())
+    };
+    if options.step {
+        stage_performance(StepStage::open(stage)?, options, recite)?;
+    } else {
+        stage_performance(stage, options, recite)?;
+    }
+    Ok(())
+}
+
+/// Stepping only works for a recitation on this terminal, so --step is refused with any command, with an export
+/// and with --estimate rather than ignored
+fn check_step(args: &[String], options: &RecitalOptions) -> Result<(), u8> {
+    if !options.step {
+        return Ok(());
+    }
+    let conflict = match args.get(COMMAND_INDEX) {
+        Some(command) if COMMANDS.contains(&command.as_str()) => Some(command.as_str()),
+        _ if options.format.is_some() => Some(FORMAT_OPTION),
+        _ if options.pace.as_ref().is_some_and(|pace| pace.estimate_only) => Some(ESTIMATE_OPTION),
+        _ => None,
+    };
+    match conflict {
+        Some(conflict) => {
+            writeln!(std::io::stderr().lock(), "Error: {} cannot be used with {}", STEP_OPTION, conflict)
+                .expect("Failed to write to stderr");
+            Err(BAD_COMMAND_LINE_ERROR)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Loads the play structure, waits for an actor per character, then cues the performance
+fn run_director(args: &[String], options: &RecitalOptions) -> Result<(), u8> {
+    let mut address = String::new();
     let mut script_filename = String::new();
+    parse_role_args(args, &mut address, &mut script_filename)?;
+    // the director never holds the lines it would have to select from
+    if !options.selection.is_empty() {
+        writeln!(std::io::stderr().lock(), "Error: {}, {} and {} cannot be used with {}",
+                 SCENE_OPTION, LINES_OPTION, ONLY_OPTION, DIRECTOR_COMMAND).expect("Failed to write to stderr");
+        usage(&args[PROGRAM_NAME_INDEX]);
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+
+    let mut director = Director::new();
+    director.prepare(&script_filename)?;
+    director.gather(&address)?;
+    stage_performance(TextStage::stdout().with_numbering(options.numbering), options,
+                      |stage| director.perform(stage))?;
+    Ok(())
+}
+
+/// Plays one character for a director listening on the given address
+fn run_actor(args: &[String]) -> Result<(), u8> {
+    let mut address = String::new();
+    let mut character_name = String::new();
+    parse_role_args(args, &mut address, &mut character_name)?;
+
+    Actor::new(&character_name).perform(&address)
+}
 
-    if let Err(error_code) = parse_args(&mut script_filename) {
+/// Prepares the play from its script, cut down to the selected scenes, lines and characters if any were given
+fn prepare_play(script_filename: &String, options: &RecitalOptions) -> Result<Play, u8> {
+    let mut play = Play::new();
+    play.prepare(script_filename)?;
+    if options.selection.is_empty() {
+        Ok(play)
+    } else {
+        select(&play, &options.selection)
+    }
+}
+
+/// Recites the play locally while streaming it to every viewer that connects to the given address
+fn run_broadcast(args: &[String], options: &RecitalOptions) -> Result<(), u8> {
+    let mut address = String::new();
+    let mut script_filename = String::new();
+    parse_role_args(args, &mut address, &mut script_filename)?;
+
+    let mut play = prepare_play(&script_filename, options)?;
+
+    let stage = BroadcastStage::open(&address)?;
+    writeln!(std::io::stderr().lock(), "Broadcasting on {}: press Enter to begin", address)
+        .expect("Failed to write to stderr");
+    let mut start = String::new();
+    let _ = std::io::stdin().read_line(&mut start);
+
+    let mut stage = stage_performance(stage, options, |stage| {
+        play.recite(stage);
+        Ok(())
+    })?;
+    stage.close();
+    Ok(())
+}
+
+/// Recites the play, pausing at each of the chosen character's lines for a human to read it
+fn run_rehearsal(args: &[String], options: &RecitalOptions) -> Result<(), u8> {
+    let mut script_filename = String::new();
+    parse_script_command_args(args, &mut script_filename)?;
+
+    let character = match options.character {
+        Some(ref character) => character.clone(),
+        None => {
+            usage(&args[PROGRAM_NAME_INDEX]);
+            return Err(BAD_COMMAND_LINE_ERROR);
+        }
+    };
+
+    let mut play = prepare_play(&script_filename, options)?;
+    if !play.characters().contains(&character) {
+        writeln!(std::io::stderr().lock(), "Error: '{}' is not a character in '{}'", character, script_filename)
+            .expect("Failed to write to stderr");
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+
+    let mut stage = RehearsalStage::new(TextStage::stdout(), &character);
+    if options.score {
+        stage = stage.with_scorecard();
+    }
+    play.recite(&mut stage);
+
+    if let Some(scorecard) = stage.scorecard() {
+        scorecard.print_summary();
+        let history_filename = match options.history {
+            Some(ref filename) => filename.clone(),
+            None => Scorecard::default_history_filename(&character),
+        };
+        scorecard.append_history(&history_filename)?;
+    }
+    Ok(())
+}
+
+/// Prints one character's cue script, or writes a cue script file for every character
+fn run_sides(args: &[String], options: &RecitalOptions) -> Result<(), u8> {
+    let mut script_filename = String::new();
+    parse_script_command_args(args, &mut script_filename)?;
+
+    let mut play = prepare_play(&script_filename, options)?;
+    let mut sides = SidesStage::new();
+    play.recite(&mut sides);
+
+    match options.character {
+        Some(ref character) => {
+            if !sides.characters().contains(character) {
+                writeln!(std::io::stderr().lock(), "Error: '{}' has no lines in '{}'", character, script_filename)
+                    .expect("Failed to write to stderr");
+                return Err(BAD_COMMAND_LINE_ERROR);
+            }
+            sides.write_sides(character, &mut std::io::stdout().lock()).expect("Failed to write to stdout");
+            Ok(())
+        }
+        None => sides.write_all(options.output.as_deref().unwrap_or(".")),
+    }
+}
+
+/// Opens the play in the full-screen browser
+fn run_browse(args: &[String], options: &RecitalOptions) -> Result<(), u8> {
+    let mut script_filename = String::new();
+    parse_script_command_args(args, &mut script_filename)?;
+
+    let mut play = prepare_play(&script_filename, options)?;
+    browse(&mut play, options.character.as_ref())
+}
+
+/// Converts a document in another format into script, config and part files
+fn run_import(args: &[String]) -> Result<(), u8> {
+    let mut source = String::new();
+    let mut directory = String::new();
+    parse_role_args(args, &mut source, &mut directory)?;
+
+    import(&source, &directory)
+}
+
+/// Packs a script with its configs and parts into one bundle file
+fn run_pack(args: &[String]) -> Result<(), u8> {
+    let mut script_filename = String::new();
+    let mut bundle_filename = String::new();
+    parse_role_args(args, &mut script_filename, &mut bundle_filename)?;
+
+    pack(&script_filename, &bundle_filename)
+}
+
+/// Writes every member of a bundle back out as a file
+fn run_unpack(args: &[String]) -> Result<(), u8> {
+    let mut bundle_filename = String::new();
+    let mut directory = String::new();
+    parse_role_args(args, &mut bundle_filename, &mut directory)?;
+
+    unpack(&bundle_filename, &directory)
+}
+
+/// Summarizes rehearsal history files by actor and scene
+fn run_report(args: &[String]) -> Result<(), u8> {
+    if args.len() < REPORT_MIN_ARGS {
+        usage(&args[PROGRAM_NAME_INDEX]);
+        return Err(BAD_COMMAND_LINE_ERROR);
+    }
+    report_history(&args[REPORT_FILES_INDEX..])
+}
+
+fn main() -> ReturnWrapper {
+    let mut args: Vec<String> = Vec::new();
+    let mut options = RecitalOptions::new();
+    if let Err(error_code) = options.parse(&env::args().collect::<Vec<String>>(), &mut args) {
+        return ReturnWrapper::new(error_code);
+    }
+    if let Err(error_code) = check_step(&args, &options) {
         return ReturnWrapper::new(error_code);
     }
 
-    let mut play = Play::new();
+    let role_result = match args.get(COMMAND_INDEX).map(String::as_str) {
+        Some(DIRECTOR_COMMAND) => Some(run_director(&args, &options)),
+        Some(ACTOR_COMMAND) => Some(run_actor(&args)),
+        Some(BROADCAST_COMMAND) => Some(run_broadcast(&args, &options)),
+        Some(REHEARSE_COMMAND) => Some(run_rehearsal(&args, &options)),
+        Some(REPORT_COMMAND) => Some(run_report(&args)),
+        Some(SIDES_COMMAND) => Some(run_sides(&args, &options)),
+        Some(BROWSE_COMMAND) => Some(run_browse(&args, &options)),
+        Some(IMPORT_COMMAND) => Some(run_import(&args)),
+        Some(PACK_COMMAND) => Some(run_pack(&args)),
+        Some(UNPACK_COMMAND) => Some(run_unpack(&args)),
+        _ => None,
+    };
+    match role_result {
+        Some(Ok(())) => return ReturnWrapper::new(SUCCESS),
+        Some(Err(error_code)) => return ReturnWrapper::new(error_code),
+        None => {}
+    }
 
-    if let Err(error_code) = play.prepare(&script_filename) {
+    let mut script_filename = String::new();
+
+    if let Err(error_code) = parse_args(&args, &mut script_filename) {
         return ReturnWrapper::new(error_code);
     }
 
-    play.recite();
+    let mut play = match prepare_play(&script_filename, &options) {
+        Ok(play) => play,
+        Err(error_code) => return ReturnWrapper::new(error_code),
+    };
+
+    if let Some(ref format) = options.format {
+        let result = if format == SSML_FORMAT {
+            export_ssml(&mut play, &options)
+        } else {
+            open_output(options.output.as_deref()).and_then(|mut out| export(format, &mut play, &options, &mut out))
+        };
+        return match result {
+            Ok(()) => ReturnWrapper::new(SUCCESS),
+            Err(error_code) => ReturnWrapper::new(error_code),
+        };
+    }
+
+    let performance = if options.terminal {
+        recite_on(&mut play, TerminalStage::stdout(options.wrap).with_numbering(options.numbering), &options)
+    } else {
+        recite_on(&mut play, TextStage::stdout().with_numbering(options.numbering), &options)
+    };
+    if let Err(error_code) = performance {
+        return ReturnWrapper::new(error_code);
+    }
 
     ReturnWrapper::new(SUCCESS)
 }
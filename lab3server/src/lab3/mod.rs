@@ -3,4 +3,5 @@
 /// Author: Zichu Pan, Edgar Palomino
 /// Summary: Module declaration for lab3server
 pub mod server;
-pub mod return_wrapper;
\ No newline at end of file
+pub mod return_wrapper;
+pub use lab3tar as tar;
@@ -7,10 +7,12 @@ use std::net::{TcpListener, TcpStream};
 use std::io::{BufRead, BufReader, Read};
 use std::fs::File;
 use std::sync::atomic::{AtomicBool, Ordering};
+use super::tar::{is_tar_archive, find_member};
 
 static CANCEL_FLAG: AtomicBool = AtomicBool::new(false);
 
 pub const FAILED_TO_BIND: u8 = 2;
+pub const MEMBER_SEPARATOR: char = '!';
 
 pub struct Server {
     listener: Option<TcpListener>,
@@ -80,10 +82,71 @@ impl Server {
             && !filename.contains('$')
     }
 
+    /// Checks a tar member name: it may name nested directories, but must stay inside the archive
+    fn is_safe_member(member: &str) -> bool {
+        !member.is_empty()
+            && !member.starts_with('/')
+            && !member.contains('\\')
+            && !member.contains('$')
+            && member.split('/').all(|component| component != "..")
+    }
+
+    /// Splits archive.tar!member into the archive and the member. Only a '!' after a tar archive name separates
+    /// a member, so other file names may contain '!'
+    fn split_member(token: &str) -> (&str, Option<&str>) {
+        token.match_indices(MEMBER_SEPARATOR)
+            .map(|(i, _)| (&token[..i], &token[i + MEMBER_SEPARATOR.len_utf8()..]))
+            .find(|(archive, _)| is_tar_archive(archive))
+            .map_or((token, None), |(archive, member)| (archive, Some(member)))
+    }
+
+    /// Reads the requested file, or the member of a tar archive when the token is archive.tar!member
+    fn read_requested(token: &str) -> Option<Vec<u8>> {
+        let (filename, member) = Self::split_member(token);
+
+        // ensure filename doesn't contain path traversal characters
+        if !Self::is_safe_filename(filename) || member.is_some_and(|m| !Self::is_safe_member(m)) {
+            let _ = writeln!(std::io::stderr().lock(), "Warning: Bad filename requested: '{}'", token);
+            return None;
+        }
+
+        let file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to open file '{}': {}", filename, e);
+                return None;
+            }
+        };
+
+        // Open file -> read file to buffer
+        let mut file_reader = BufReader::new(file);
+        let mut buffer = Vec::new();
+        if let Err(e) = file_reader.read_to_end(&mut buffer) {
+            let _ = writeln!(std::io::stderr().lock(), "Error: Failed to read file '{}': {}", filename, e);
+            return None;
+        }
+
+        let member = match member {
+            Some(member) => member,
+            None => return Some(buffer),
+        };
+        match find_member(&buffer, member) {
+            Ok(Some(contents)) => Some(contents),
+            Ok(None) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: '{}' has no member '{}'", filename, member);
+                None
+            }
+            Err(message) => {
+                let _ = writeln!(std::io::stderr().lock(), "Error: Invalid archive '{}': {}", filename, message);
+                None
+            }
+        }
+    }
+
     /// Handles a single client connection
     /// - Reads a token from the connection
     /// - If "quit", sets CANCEL_FLAG and returns
-    /// - Otherwise, treats token as filename and streams file contents
+    /// - Otherwise, treats token as filename (or archive.tar!member) and streams its contents
     fn handle_connection(mut stream: TcpStream) {
         let mut reader = BufReader::new(&stream);
         let mut token = String::new();
@@ -102,34 +165,57 @@ impl Server {
             return;
         }
         
-        // ensure filename doesn't contain path traversal characters
-        if !Self::is_safe_filename(token) {
-            let _ = writeln!(std::io::stderr().lock(), "Warning: Bad filename requested: '{}'", token);
-            let _ = stream.shutdown(std::net::Shutdown::Both);
-            return;
-        }
-        
-        let file = match File::open(token) {
-            Ok(f) => f,
-            Err(e) => {
-                let _ = writeln!(std::io::stderr().lock(), "Error: Failed to open file '{}': {}", token, e);
+        let buffer = match Self::read_requested(token) {
+            Some(buffer) => buffer,
+            None => {
                 let _ = stream.shutdown(std::net::Shutdown::Both);
                 return;
             }
         };
-        
-        // Open file -> read file to buffer
-        let mut file_reader = BufReader::new(file);
-        let mut buffer = Vec::new();
-        if let Err(e) = file_reader.read_to_end(&mut buffer) {
-            let _ = writeln!(std::io::stderr().lock(), "Error: Failed to read file '{}': {}", token, e);
-            let _ = stream.shutdown(std::net::Shutdown::Both);
-            return;
-        }
         if let Err(e) = stream.write_all(&buffer) {
             let _ = writeln!(std::io::stderr().lock(), "Error: Failed to write to connection: {}", e);
         }
         
         let _ = stream.shutdown(std::net::Shutdown::Both);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_must_stay_inside_the_archive() {
+        assert!(Server::is_safe_member("hamlet_ii_1a_config.txt"));
+        assert!(Server::is_safe_member("sub/Polonius.txt"));
+        assert!(Server::is_safe_member("sub/..hidden.txt"));
+        assert!(!Server::is_safe_member(""));
+        assert!(!Server::is_safe_member("/etc/passwd"));
+        assert!(!Server::is_safe_member(".."));
+        assert!(!Server::is_safe_member("../secret.txt"));
+        assert!(!Server::is_safe_member("sub/../../secret.txt"));
+        assert!(!Server::is_safe_member("sub\\..\\secret.txt"));
+    }
+
+    #[test]
+    fn unsafe_requests_are_refused() {
+        assert!(Server::read_requested("../Cargo.toml").is_none());
+        assert!(Server::read_requested("/etc/passwd").is_none());
+        assert!(Server::read_requested("play.tar!../Cargo.toml").is_none());
+        assert!(Server::read_requested("play.tar!/etc/passwd").is_none());
+        assert!(Server::read_requested("Cargo.toml!src/main.rs").is_none());
+    }
+
+    #[test]
+    fn only_tar_archives_have_members() {
+        assert_eq!(Server::split_member("play.tar!sub/part.txt"), ("play.tar", Some("sub/part.txt")));
+        assert_eq!(Server::split_member("wow!_part.txt"), ("wow!_part.txt", None));
+        assert_eq!(Server::split_member("wow!.tar!part.txt"), ("wow!.tar", Some("part.txt")));
+
+        let filename = "server_test_wow!_part.txt";
+        std::fs::write(filename, "1 Hello there.\n").unwrap();
+        let served = Server::read_requested(filename);
+        let _ = std::fs::remove_file(filename);
+        assert_eq!(served, Some(b"1 Hello there.\n".to_vec()));
+    }
+}
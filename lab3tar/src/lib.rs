@@ -0,0 +1,193 @@
+/// lib.rs
+/// Author: Zichu Pan, Edgar Palomino
+/// Summary: A small reader for tar archives (ustar, with GNU long names and pax path records), enough to read
+/// a play's files out of a tarball without any external crates. The client reads archives it opens locally
+/// with it and the server reads the members it serves, so both crates depend on this one.
+const BLOCK_SIZE: usize = 512;
+const NAME_RANGE: (usize, usize) = (0, 100);
+const SIZE_RANGE: (usize, usize) = (124, 136);
+const CHECKSUM_RANGE: (usize, usize) = (148, 156);
+const TYPE_OFFSET: usize = 156;
+const MAGIC_RANGE: (usize, usize) = (257, 263);
+const PREFIX_RANGE: (usize, usize) = (345, 500);
+const USTAR_MAGIC: &[u8] = b"ustar";
+const PAX_PATH_KEY: &str = "path";
+
+pub const TAR_EXTENSION: &str = ".tar";
+
+pub type TarMembers = Vec<(String, Vec<u8>)>;   // (member_name, contents)
+
+pub fn is_tar_archive(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(TAR_EXTENSION)
+}
+
+/// Member names are compared without any leading "./"
+pub fn normalize_member_name(name: &str) -> &str {
+    let mut name = name;
+    while let Some(rest) = name.strip_prefix("./") {
+        name = rest;
+    }
+    name
+}
+
+fn field(header: &[u8], range: (usize, usize)) -> &[u8] {
+    let bytes = &header[range.0..range.1];
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    &bytes[..end]
+}
+
+fn text_field(header: &[u8], range: (usize, usize)) -> String {
+    String::from_utf8_lossy(field(header, range)).to_string()
+}
+
+/// Numeric fields are octal text, or big-endian binary when the high bit of the first byte is set
+fn number_field(header: &[u8], range: (usize, usize)) -> Result<usize, String> {
+    let bytes = &header[range.0..range.1];
+    if bytes[0] & 0x80 != 0 {
+        let mut value: usize = (bytes[0] & 0x7f) as usize;
+        for byte in &bytes[1..] {
+            value = value.checked_mul(256).and_then(|v| v.checked_add(*byte as usize))
+                .ok_or("numeric field is too large")?;
+        }
+        return Ok(value);
+    }
+    let text = String::from_utf8_lossy(field(header, range)).trim().to_string();
+    if text.is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(&text, 8).map_err(|_| format!("invalid numeric field '{}'", text))
+}
+
+fn check_header(header: &[u8]) -> Result<(), String> {
+    let expected = number_field(header, CHECKSUM_RANGE)?;
+    let sum: usize = header.iter().enumerate()
+        .map(|(i, b)| if (CHECKSUM_RANGE.0..CHECKSUM_RANGE.1).contains(&i) { b' ' as usize } else { *b as usize })
+        .sum();
+    if sum != expected {
+        return Err(format!("header checksum mismatch for '{}'", text_field(header, NAME_RANGE)));
+    }
+    Ok(())
+}
+
+/// The path from a pax extended header, made of "<length> <key>=<value>\n" records
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines().find_map(|record| {
+        let (_, pair) = record.split_once(' ')?;
+        let (key, value) = pair.split_once('=')?;
+        if key == PAX_PATH_KEY { Some(value.to_string()) } else { None }
+    })
+}
+
+/// Reads every regular file in the archive
+pub fn parse_tar(bytes: &[u8]) -> Result<TarMembers, String> {
+    let mut members: TarMembers = Vec::new();
+    let mut position = 0;
+    let mut long_name: Option<String> = None;
+
+    while position + BLOCK_SIZE <= bytes.len() {
+        let header = &bytes[position..position + BLOCK_SIZE];
+        if header.iter().all(|b| *b == 0) {
+            break;  // end of archive
+        }
+        check_header(header)?;
+
+        let size = number_field(header, SIZE_RANGE)?;
+        let data_start = position + BLOCK_SIZE;
+        if data_start + size > bytes.len() {
+            return Err(format!("member '{}' is truncated", text_field(header, NAME_RANGE)));
+        }
+        let data = &bytes[data_start..data_start + size];
+        position = data_start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+
+        let mut name = text_field(header, NAME_RANGE);
+        if field(header, MAGIC_RANGE).starts_with(USTAR_MAGIC) {
+            let prefix = text_field(header, PREFIX_RANGE);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+        match header[TYPE_OFFSET] {
+            b'L' => long_name = Some(String::from_utf8_lossy(data).trim_end_matches('\0').to_string()),
+            b'x' => long_name = pax_path(data).or(long_name),
+            b'0' | b'\0' | b'7' => {
+                let name = long_name.take().unwrap_or(name);
+                members.push((normalize_member_name(&name).to_string(), data.to_vec()));
+            }
+            _ => long_name = None,    // directories, links and other entries hold no play files
+        }
+    }
+    Ok(members)
+}
+
+/// Finds one member of the archive
+pub fn find_member(bytes: &[u8], member: &str) -> Result<Option<Vec<u8>>, String> {
+    let member = normalize_member_name(member);
+    Ok(parse_tar(bytes)?.into_iter().find(|(name, _)| name == member).map(|(_, contents)| contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODE_RANGE: (usize, usize) = (100, 108);
+
+    /// One header block followed by the data, padded to whole blocks, as tar writes them
+    fn entry(name: &str, prefix: &str, kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut header = vec![0u8; BLOCK_SIZE];
+        header[NAME_RANGE.0..NAME_RANGE.0 + name.len()].copy_from_slice(name.as_bytes());
+        header[MODE_RANGE.0..MODE_RANGE.1 - 1].copy_from_slice(b"0000644");
+        header[SIZE_RANGE.0..SIZE_RANGE.1 - 1].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[TYPE_OFFSET] = kind;
+        header[MAGIC_RANGE.0..MAGIC_RANGE.1].copy_from_slice(b"ustar\0");
+        header[PREFIX_RANGE.0..PREFIX_RANGE.0 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        header[CHECKSUM_RANGE.0..CHECKSUM_RANGE.1].fill(b' ');
+        let checksum: usize = header.iter().map(|b| *b as usize).sum();
+        header[CHECKSUM_RANGE.0..CHECKSUM_RANGE.1 - 1].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+
+        let mut bytes = header;
+        bytes.extend_from_slice(data);
+        bytes.resize(bytes.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        bytes
+    }
+
+    fn archive(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = entries.concat();
+        bytes.extend_from_slice(&[0u8; 2 * BLOCK_SIZE]);
+        bytes
+    }
+
+    #[test]
+    fn members_round_trip() {
+        let long_name = format!("sub/{}/Polonius.txt", "deep".repeat(30));
+        let pax_name = format!("sub/{}/Ophelia.txt", "pax".repeat(40));
+        let pax_record = format!("{} path={}\n", pax_name.len() + 10, pax_name);
+        let part = b"1 Give him this money and these notes, Reynaldo.\n".repeat(20);
+        let bytes = archive(&[
+            entry("./script.txt", "", b'0', b"Act II Scene I\nconfig.txt\n"),
+            entry("sub/", "", b'5', b""),
+            entry("Reynaldo.txt", "sub", b'0', b"2 Well, my lord.\n"),
+            entry("././@LongLink", "", b'L', format!("{}\0", long_name).as_bytes()),
+            entry("truncated", "", b'0', &part),
+            entry("PaxHeader", "", b'x', pax_record.as_bytes()),
+            entry("short", "", b'0', b""),
+        ]);
+        let members = parse_tar(&bytes).unwrap();
+        let names: Vec<&str> = members.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["script.txt", "sub/Reynaldo.txt", long_name.as_str(), pax_name.as_str()]);
+        assert_eq!(members[2].1, part);
+        assert_eq!(find_member(&bytes, "./sub/Reynaldo.txt"), Ok(Some(b"2 Well, my lord.\n".to_vec())));
+        assert_eq!(find_member(&bytes, "missing.txt"), Ok(None));
+    }
+
+    #[test]
+    fn damaged_archives_are_refused() {
+        let mut bytes = archive(&[entry("script.txt", "", b'0', b"config.txt\n")]);
+        bytes[0] = b'S';
+        assert!(parse_tar(&bytes).unwrap_err().starts_with("header checksum mismatch"));
+
+        let mut bytes = entry("script.txt", "", b'0', &[b'x'; 600]);
+        bytes.truncate(BLOCK_SIZE + 100);
+        assert!(parse_tar(&bytes).unwrap_err().contains("truncated"));
+    }
+}